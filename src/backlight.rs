@@ -0,0 +1,314 @@
+//! The backlight isn't part of the display controller, it's usually just a
+//! GPIO (or a PWM channel) on the module, so it gets its own trait.
+//!
+//! [`Fade`] moves a backlight to a new level a little at a time, and
+//! [`DimmingPolicy`] decides what that level should be, e.g.
+//! ```no_run
+//! let policy = DimmingPolicy::default();
+//! let mut fade = Fade::new(4);
+//! loop {
+//!     // ...
+//!     fade.set_target(policy.level(minute_of_day, idle_ms));
+//!     fade.step(&mut backlight)?;
+//!     delay.delay_ms(20);
+//! }
+//! ```
+
+use core::fmt::Debug;
+
+use embedded_hal::{delay::DelayNs, digital::OutputPin, pwm::SetDutyCycle};
+
+pub trait Backlight {
+    type Error: Debug;
+
+    /// 0 is off, 255 is as bright as it goes
+    fn set_brightness(&mut self, level: u8) -> Result<(), Self::Error>;
+
+    /// The level we last set
+    fn brightness(&self) -> u8;
+}
+
+/// A backlight on a plain GPIO, so anything but 0 is fully on
+pub struct PinBacklight<P: OutputPin> {
+    pub pin: P,
+    level: u8,
+}
+
+impl<P: OutputPin> PinBacklight<P> {
+    /// We don't know what the pin is doing yet, so this says it's off until
+    /// the first [`Backlight::set_brightness`]
+    pub fn new(pin: P) -> Self {
+        Self { pin, level: 0 }
+    }
+}
+
+impl<P: OutputPin> Backlight for PinBacklight<P> {
+    type Error = P::Error;
+
+    fn set_brightness(&mut self, level: u8) -> Result<(), Self::Error> {
+        if level == 0 {
+            self.pin.set_low()?;
+        } else {
+            self.pin.set_high()?;
+        }
+        self.level = level;
+        Ok(())
+    }
+
+    fn brightness(&self) -> u8 {
+        self.level
+    }
+}
+
+/// A backlight driven by PWM, e.g. an LEDC channel
+pub struct PwmBacklight<P: SetDutyCycle> {
+    pub pwm: P,
+    level: u8,
+}
+
+impl<P: SetDutyCycle> PwmBacklight<P> {
+    /// We don't know what the duty cycle is yet, so this says it's off until
+    /// the first [`Backlight::set_brightness`]
+    pub fn new(pwm: P) -> Self {
+        Self { pwm, level: 0 }
+    }
+}
+
+impl<P: SetDutyCycle> Backlight for PwmBacklight<P> {
+    type Error = P::Error;
+
+    fn set_brightness(&mut self, level: u8) -> Result<(), Self::Error> {
+        self.pwm
+            .set_duty_cycle_fraction(level as u16, u8::MAX as u16)?;
+        self.level = level;
+        Ok(())
+    }
+
+    fn brightness(&self) -> u8 {
+        self.level
+    }
+}
+
+/// Moves a backlight towards a target level by at most `rate` per
+/// [`Fade::step`], so it doesn't jump when the level changes. Call `step`
+/// every time round the main loop.
+pub struct Fade {
+    target: u8,
+    rate: u8,
+}
+
+impl Fade {
+    /// Panics if `rate` is 0
+    pub fn new(rate: u8) -> Self {
+        assert!(rate != 0);
+        Self {
+            target: u8::MAX,
+            rate,
+        }
+    }
+
+    pub fn set_target(&mut self, level: u8) {
+        self.target = level;
+    }
+
+    pub fn target(&self) -> u8 {
+        self.target
+    }
+
+    /// Move `backlight` one step closer to the target, and return whether
+    /// there is further to go. Does nothing if we're already there.
+    pub fn step<B: Backlight>(&self, backlight: &mut B) -> Result<bool, B::Error> {
+        let level = backlight.brightness();
+        let next = if level < self.target {
+            level.saturating_add(self.rate).min(self.target)
+        } else {
+            level.saturating_sub(self.rate).max(self.target)
+        };
+        if next != level {
+            backlight.set_brightness(next)?;
+        }
+        Ok(next != self.target)
+    }
+}
+
+/// Fade `backlight` to `level` over about `duration_ms`, blocking until it
+/// gets there
+pub fn fade_to<B: Backlight>(
+    backlight: &mut B,
+    level: u8,
+    duration_ms: u32,
+    delay: &mut impl DelayNs,
+) -> Result<(), B::Error> {
+    let distance = backlight.brightness().abs_diff(level) as u32;
+    if distance == 0 {
+        return Ok(());
+    }
+    let step_ms = duration_ms / distance;
+    let mut fade = Fade::new(1);
+    fade.set_target(level);
+    while fade.step(backlight)? {
+        delay.delay_ms(step_ms);
+    }
+    Ok(())
+}
+
+/// How bright the backlight should be: dimmer at night, and dimmer still
+/// when nothing on the display has changed for a while
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DimmingPolicy {
+    pub day: u8,
+    pub night: u8,
+    /// never brighter than this once we're idle, day or night
+    pub idle: u8,
+    /// night is from `night_start` until `night_end`, in minutes since
+    /// midnight. It can wrap round past midnight.
+    pub night_start: u16,
+    pub night_end: u16,
+    pub idle_timeout_ms: u32,
+}
+
+impl Default for DimmingPolicy {
+    /// Full brightness in the day, dim from 22:00 to 06:00, and dimmer
+    /// still after 5 minutes without a change
+    fn default() -> Self {
+        Self {
+            day: u8::MAX,
+            night: 64,
+            idle: 16,
+            night_start: 22 * 60,
+            night_end: 6 * 60,
+            idle_timeout_ms: 5 * 60 * 1000,
+        }
+    }
+}
+
+impl DimmingPolicy {
+    pub fn is_night(&self, minute_of_day: u16) -> bool {
+        if self.night_start <= self.night_end {
+            (self.night_start..self.night_end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.night_start || minute_of_day < self.night_end
+        }
+    }
+
+    /// The level we want at `minute_of_day` (minutes since midnight), when
+    /// the display last changed `idle_ms` ago
+    pub fn level(&self, minute_of_day: u16, idle_ms: u32) -> u8 {
+        let level = if self.is_night(minute_of_day) {
+            self.night
+        } else {
+            self.day
+        };
+        if idle_ms >= self.idle_timeout_ms {
+            level.min(self.idle)
+        } else {
+            level
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use super::*;
+
+    /// A backlight that isn't there, which remembers what it was set to
+    #[derive(Default)]
+    pub struct MockBacklight {
+        level: u8,
+        /// how many times the level has been set
+        pub writes: usize,
+    }
+
+    impl Backlight for MockBacklight {
+        type Error = Infallible;
+
+        fn set_brightness(&mut self, level: u8) -> Result<(), Self::Error> {
+            self.level = level;
+            self.writes += 1;
+            Ok(())
+        }
+
+        fn brightness(&self) -> u8 {
+            self.level
+        }
+    }
+
+    /// Adds up how long we were asked to wait
+    #[derive(Default)]
+    struct TotalDelay {
+        ms: u32,
+    }
+
+    impl DelayNs for TotalDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.ms += ns / 1_000_000;
+        }
+
+        fn delay_ms(&mut self, ms: u32) {
+            self.ms += ms;
+        }
+    }
+
+    #[test]
+    fn fade_steps_to_the_target() {
+        let mut backlight = MockBacklight::default();
+        let mut fade = Fade::new(4);
+        fade.set_target(10);
+
+        assert!(fade.step(&mut backlight).unwrap());
+        assert_eq!(backlight.brightness(), 4);
+        assert!(fade.step(&mut backlight).unwrap());
+        assert_eq!(backlight.brightness(), 8);
+        // the last step is cut short so we don't overshoot
+        assert!(!fade.step(&mut backlight).unwrap());
+        assert_eq!(backlight.brightness(), 10);
+
+        // and once we're there, nothing is written
+        assert!(!fade.step(&mut backlight).unwrap());
+        assert_eq!(backlight.writes, 3);
+
+        fade.set_target(0);
+        while fade.step(&mut backlight).unwrap() {}
+        assert_eq!(backlight.brightness(), 0);
+        assert_eq!(backlight.writes, 6);
+    }
+
+    #[test]
+    fn fade_to_takes_about_the_duration() {
+        let mut backlight = MockBacklight::default();
+        let mut delay = TotalDelay::default();
+        fade_to(&mut backlight, 100, 1000, &mut delay).unwrap();
+        assert_eq!(backlight.brightness(), 100);
+        assert_eq!(backlight.writes, 100);
+        // no need to wait after the last step
+        assert_eq!(delay.ms, 990);
+
+        fade_to(&mut backlight, 100, 1000, &mut delay).unwrap();
+        assert_eq!(backlight.writes, 100);
+    }
+
+    #[test]
+    fn dimming_policy_levels() {
+        let policy = DimmingPolicy::default();
+        let idle = policy.idle_timeout_ms;
+        assert_eq!(policy.level(12 * 60, 0), u8::MAX);
+        assert_eq!(policy.level(23 * 60, 0), 64);
+        assert_eq!(policy.level(5 * 60 + 59, 0), 64);
+        assert_eq!(policy.level(6 * 60, 0), u8::MAX);
+        assert_eq!(policy.level(12 * 60, idle - 1), u8::MAX);
+        assert_eq!(policy.level(12 * 60, idle), 16);
+        assert_eq!(policy.level(23 * 60, idle), 16);
+
+        // a night that doesn't wrap round midnight
+        let policy = DimmingPolicy {
+            night_start: 60,
+            night_end: 5 * 60,
+            ..policy
+        };
+        assert!(!policy.is_night(0));
+        assert!(policy.is_night(60));
+        assert!(!policy.is_night(5 * 60));
+    }
+}
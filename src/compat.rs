@@ -0,0 +1,146 @@
+//! `esp32s3_hal` implements the `embedded_hal` 0.2 traits, but the drivers
+//! are written against `embedded_hal` 1.0. These wrappers bridge the two.
+
+use core::{convert::Infallible, fmt::Debug};
+
+use embedded_hal::{delay, digital, spi};
+use esp32s3_hal::prelude::*;
+
+use crate::types::{InputPinV2, OutputPinV2};
+
+/// An [`spi::SpiDevice`] over an `embedded_hal` 0.2 blocking SPI bus, which
+/// drives CS itself so that it stays low for a whole transaction. A bus made
+/// with `with_cs` would raise it between a command and reading the reply,
+/// and the controller gives up on the read. The firmware only reads with
+/// the `self-test` feature, and uses DMA otherwise.
+#[cfg_attr(not(feature = "self-test"), allow(dead_code))]
+pub struct SpiV2<S, Cs> {
+    pub spi: S,
+    pub cs: Cs,
+}
+
+#[cfg_attr(not(feature = "self-test"), allow(dead_code))]
+impl<S, Cs: digital::OutputPin<Error = Infallible>> SpiV2<S, Cs> {
+    /// Takes CS high, i.e. not selected, until the first transaction
+    pub fn new(spi: S, mut cs: Cs) -> Self {
+        let Ok(()) = cs.set_high();
+        Self { spi, cs }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(not(feature = "self-test"), allow(dead_code))]
+pub enum SpiV2Error<E> {
+    Bus(E),
+    /// `Operation::DelayNs` inside a transaction, we have no way of doing it
+    Unsupported,
+}
+
+impl<E: Debug> spi::Error for SpiV2Error<E> {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+impl<S, Cs, E> spi::ErrorType for SpiV2<S, Cs>
+where
+    S: _embedded_hal_blocking_spi_Write<u8, Error = E>
+        + _embedded_hal_blocking_spi_Transfer<u8, Error = E>,
+    E: Debug,
+{
+    type Error = SpiV2Error<E>;
+}
+
+#[cfg_attr(not(feature = "self-test"), allow(dead_code))]
+impl<S, Cs, E> SpiV2<S, Cs>
+where
+    S: _embedded_hal_blocking_spi_Write<u8, Error = E>
+        + _embedded_hal_blocking_spi_Transfer<u8, Error = E>,
+    E: Debug,
+{
+    fn operation(&mut self, op: &mut spi::Operation<'_, u8>) -> Result<(), SpiV2Error<E>> {
+        match op {
+            spi::Operation::Write(bytes) => self.spi.write(bytes).map_err(SpiV2Error::Bus)?,
+            spi::Operation::Read(buf) => {
+                buf.fill(0);
+                self.spi.transfer(buf).map_err(SpiV2Error::Bus)?;
+            }
+            spi::Operation::TransferInPlace(buf) => {
+                self.spi.transfer(buf).map_err(SpiV2Error::Bus)?;
+            }
+            spi::Operation::Transfer(read, write) => {
+                // 0.2 only has in-place transfers, so do it in `read` and
+                // clock out anything left over in `write` afterwards
+                let n = read.len().min(write.len());
+                read[..n].copy_from_slice(&write[..n]);
+                read[n..].fill(0);
+                self.spi.transfer(read).map_err(SpiV2Error::Bus)?;
+                self.spi.write(&write[n..]).map_err(SpiV2Error::Bus)?;
+            }
+            spi::Operation::DelayNs(_) => return Err(SpiV2Error::Unsupported),
+        }
+        Ok(())
+    }
+}
+
+impl<S, Cs, E> spi::SpiDevice for SpiV2<S, Cs>
+where
+    S: _embedded_hal_blocking_spi_Write<u8, Error = E>
+        + _embedded_hal_blocking_spi_Transfer<u8, Error = E>,
+    Cs: digital::OutputPin<Error = Infallible>,
+    E: Debug,
+{
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let Ok(()) = self.cs.set_low();
+        let result = operations.iter_mut().try_for_each(|op| self.operation(op));
+        // the 0.2 writes block until the bytes are out, so this can't cut
+        // the last one short
+        let Ok(()) = self.cs.set_high();
+        result
+    }
+}
+
+/// A [`digital::OutputPin`] or [`digital::InputPin`] over an `embedded_hal`
+/// 0.2 pin that can't fail, which is every `esp32s3_hal` GPIO
+pub struct PinV2<P>(pub P);
+
+impl<P> digital::ErrorType for PinV2<P> {
+    type Error = Infallible;
+}
+
+impl<P: OutputPinV2<Error = Infallible>> digital::OutputPin for PinV2<P> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
+    }
+}
+
+impl<P: InputPinV2<Error = Infallible>> digital::InputPin for PinV2<P> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.0.is_low()
+    }
+}
+
+/// A [`delay::DelayNs`] over an `embedded_hal` 0.2 microsecond delay, e.g.
+/// `esp32s3_hal::Delay`. Nanosecond delays are rounded up.
+pub struct DelayV2<D>(pub D);
+
+impl<D: _embedded_hal_blocking_delay_DelayUs<u32>> delay::DelayNs for DelayV2<D> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0.delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.0.delay_us(us);
+    }
+}
@@ -0,0 +1,372 @@
+//! What every controller driver has in common. A driver is a [`Driver`]
+//! with a [`Controller`] that says what's different about its chip, e.g.
+//! [`crate::st7789::St7789`] is a `Driver<st7789::Chip, _, _>`. Most of
+//! the controllers here speak MIPI DCS, so that's what [`Controller`] does
+//! unless a chip says otherwise.
+
+use core::marker::PhantomData;
+
+use embedded_hal::{delay::DelayNs, digital::OutputPin};
+
+use crate::{
+    init_sequence::InitSequence,
+    interface::{self, DisplayInterface, Error, ResetTiming},
+    lcd::{self, Lcd, PowerManagement, Rect, Rgb16, SafeArea},
+    panel::PanelConfig,
+    pixel_format::{PixelEncoder, PixelFormat},
+};
+
+/// The parts of a controller that differ from chip to chip. The default
+/// methods are the MIPI DCS ones.
+pub trait Controller {
+    /// From the datasheet, see [`interface::hardware_reset`]
+    const RESET_TIMING: ResetTiming;
+
+    /// What [`PanelConfig::pixel_format`] can be, checked by [`Driver::init`]
+    const PIXEL_FORMATS: &'static [PixelFormat] = &[
+        PixelFormat::Rgb565,
+        PixelFormat::Rgb666,
+        PixelFormat::Rgb444,
+    ];
+
+    /// Send `command` and its parameters
+    fn write_command<DI: DisplayInterface>(
+        iface: &mut DI,
+        command: u8,
+        params: &[u8],
+    ) -> Result<(), Error<DI::BusError>> {
+        iface.write_command(command, params)
+    }
+
+    /// Set up everything that comes from `config`, after the init
+    /// sequence, whatever the sequence set it to
+    fn configure<DI: DisplayInterface>(
+        iface: &mut DI,
+        config: &PanelConfig,
+    ) -> Result<(), Error<DI::BusError>> {
+        iface.write_command(MADCTL, &[config.madctl()])?;
+        iface.write_command(COLMOD, &[config.pixel_format.colmod()])?;
+        let inversion = if config.inverted { INVON } else { INVOFF };
+        iface.write_command(inversion, &[])
+    }
+
+    /// Set the window x and y ranges (inclusive, in rotated coordinates)
+    fn set_window<DI: DisplayInterface>(
+        iface: &mut DI,
+        config: &PanelConfig,
+        x: (u16, u16),
+        y: (u16, u16),
+    ) -> Result<(), Error<DI::BusError>> {
+        let (x_offset, y_offset) = config.offset();
+        let x = (x.0 + x_offset, x.1 + x_offset);
+        let y = (y.0 + y_offset, y.1 + y_offset);
+        iface.set_address_window(x, y)
+    }
+
+    /// Get ready for pixels to go into the window
+    fn start_write<DI: DisplayInterface>(iface: &mut DI) -> Result<(), Error<DI::BusError>> {
+        iface.write_commands(&[RAMWR])
+    }
+
+    fn safe_area() -> SafeArea {
+        SafeArea::Rectangle
+    }
+}
+
+/// A [`Controller`] with the MIPI DCS sleep, idle and partial mode
+/// commands, which is all [`PowerManagement`] needs. The delays are from
+/// the chip's datasheet, and its presets wait `SLPOUT_DELAY_MS` after their
+/// `SLPOUT` too.
+pub trait DcsPower: Controller {
+    /// How long to wait after `SLPIN` before sending anything else
+    const SLPIN_DELAY_MS: u32;
+    /// How long to wait after `SLPOUT` for the supply voltages and clocks to
+    /// settle, before sending anything else
+    const SLPOUT_DELAY_MS: u32;
+}
+
+/// A display with controller `C` over any [`DisplayInterface`]. `rst` can be
+/// [`crate::lcd::NoPin`] if the reset line isn't connected. The backlight is
+/// wired up separately from the controller, see [`crate::backlight`].
+pub struct Driver<C: Controller, DI: DisplayInterface, Rst: OutputPin> {
+    pub iface: DI,
+    pub rst: Rst,
+    pub config: PanelConfig,
+    /// what [`Driver::init`] sends, e.g. [`crate::st7789::presets::PIMORONI`]
+    pub sequence: InitSequence<'static>,
+    /// for when `config.pixel_format` isn't what we draw in
+    encoder: PixelEncoder,
+    controller: PhantomData<C>,
+}
+
+impl<C, DI, Rst> Driver<C, DI, Rst>
+where
+    C: Controller,
+    DI: DisplayInterface,
+    Rst: OutputPin,
+{
+    /// Nothing is sent until [`Driver::init`]
+    pub fn new(iface: DI, rst: Rst, config: PanelConfig, sequence: InitSequence<'static>) -> Self {
+        Self {
+            iface,
+            rst,
+            config,
+            sequence,
+            encoder: PixelEncoder::new(),
+            controller: PhantomData,
+        }
+    }
+
+    /// Reset the panel and send it [`Driver::sequence`]. Whatever rotation
+    /// and inversion the sequence sets, we set them from `config`
+    /// afterwards. If the controller can't do `config.pixel_format`, this
+    /// fails with [`Error::Unsupported`] before sending anything.
+    pub fn init(&mut self, delay: &mut impl DelayNs) -> Result<(), Error<DI::BusError>> {
+        if !C::PIXEL_FORMATS.contains(&self.config.pixel_format) {
+            return Err(Error::Unsupported);
+        }
+        interface::hardware_reset(&mut self.rst, C::RESET_TIMING, delay)?;
+        self.encoder = PixelEncoder::new();
+        for step in self.sequence.commands() {
+            C::write_command(&mut self.iface, step.command, step.data)?;
+            self.iface.flush()?;
+            if step.delay_ms != 0 {
+                delay.delay_ms(step.delay_ms);
+            }
+        }
+        C::configure(&mut self.iface, &self.config)
+    }
+
+    /// [`Driver::init`] with a different `sequence`, which we keep for next
+    /// time
+    pub fn init_with(
+        &mut self,
+        sequence: InitSequence<'static>,
+        delay: &mut impl DelayNs,
+    ) -> Result<(), Error<DI::BusError>> {
+        self.sequence = sequence;
+        self.init(delay)
+    }
+
+    /// Start the panel again from scratch, e.g. when ESD has scrambled its
+    /// registers, without restarting the MCU. Everything on the display is
+    /// lost, so redraw it afterwards, see [`crate::term::Term::invalidate`].
+    pub fn reinit(&mut self, delay: &mut impl DelayNs) -> Result<(), Error<DI::BusError>> {
+        self.init(delay)
+    }
+
+    /// Set the window x and y ranges (inclusive, in rotated coordinates),
+    /// after any pixels we're holding on to, e.g.
+    /// ```no_run
+    /// lcd.set_window((0, 239), (0, 239));
+    /// ```
+    pub fn set_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Error<DI::BusError>> {
+        self.finish_pixels()?;
+        C::set_window(&mut self.iface, &self.config, x, y)
+    }
+
+    /// Send `command` with `params`, after any pixels we're holding on to
+    pub(crate) fn command(
+        &mut self,
+        command: u8,
+        params: &[u8],
+    ) -> Result<(), Error<DI::BusError>> {
+        self.finish_pixels()?;
+        C::write_command(&mut self.iface, command, params)
+    }
+
+    /// Send anything [`Driver::write_rgb`](Lcd::write_rgb) is holding on
+    /// to, before we send a command
+    pub(crate) fn finish_pixels(&mut self) -> Result<(), Error<DI::BusError>> {
+        let iface = &mut self.iface;
+        self.encoder.finish(|bytes| iface.write_data(bytes))
+    }
+}
+
+impl<C, DI, Rst> Lcd for Driver<C, DI, Rst>
+where
+    C: Controller,
+    DI: DisplayInterface,
+    Rst: OutputPin,
+{
+    type Error = Error<DI::BusError>;
+
+    fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error> {
+        self.set_window(x, y)?;
+        C::start_write(&mut self.iface)
+    }
+
+    fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error> {
+        match self.config.pixel_format {
+            PixelFormat::Rgb565 => self.iface.write_pixels(rgb),
+            format => {
+                let iface = &mut self.iface;
+                self.encoder
+                    .write(format, rgb, |bytes| iface.write_data(bytes))
+            }
+        }
+    }
+
+    fn write_rgb_with(
+        &mut self,
+        count: usize,
+        fill: &mut dyn FnMut(usize, &mut [Rgb16]),
+    ) -> Result<(), Self::Error> {
+        match self.config.pixel_format {
+            PixelFormat::Rgb565 => self.iface.write_pixels_with(count, fill),
+            _ => lcd::write_with(count, fill, |rgb| self.write_rgb(rgb)),
+        }
+    }
+
+    /// Send the odd pixel an RGB444 encoder might be waiting to pair up,
+    /// then whatever the bus is holding on to
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.finish_pixels()?;
+        self.iface.flush()
+    }
+
+    fn dimensions(&self) -> (u16, u16) {
+        self.config.dimensions()
+    }
+
+    fn safe_area(&self) -> SafeArea {
+        C::safe_area()
+    }
+
+    /// Streams `colour` straight to the interface, which might not need a
+    /// buffer at all, unless we have to convert it to another pixel format.
+    /// It's clipped like [`Lcd::fill_rect`] says.
+    fn fill_rect(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        colour: Rgb16,
+    ) -> Result<(), Self::Error> {
+        let Some(rect) = lcd::clip(self, Rect::new(x, y, w, h)) else {
+            return Ok(());
+        };
+        let (x, y) = rect.window();
+        self.prepare_window(x, y)?;
+        let count = rect.area();
+        match self.config.pixel_format {
+            PixelFormat::Rgb565 => self.iface.write_repeated(colour, count)?,
+            _ => lcd::write_repeated(self, colour, count)?,
+        }
+        self.flush()
+    }
+}
+
+impl<C, DI, Rst> PowerManagement for Driver<C, DI, Rst>
+where
+    C: DcsPower,
+    DI: DisplayInterface,
+    Rst: OutputPin,
+{
+    fn sleep(&mut self, delay: &mut impl DelayNs) -> Result<(), Self::Error> {
+        self.command(SLPIN, &[])?;
+        self.iface.flush()?;
+        delay.delay_ms(C::SLPIN_DELAY_MS);
+        Ok(())
+    }
+
+    fn wake(&mut self, delay: &mut impl DelayNs) -> Result<(), Self::Error> {
+        self.command(SLPOUT, &[])?;
+        self.iface.flush()?;
+        delay.delay_ms(C::SLPOUT_DELAY_MS);
+        Ok(())
+    }
+
+    fn set_display_on(&mut self, on: bool) -> Result<(), Self::Error> {
+        self.command(if on { DISPON } else { DISPOFF }, &[])
+    }
+
+    fn set_idle(&mut self, idle: bool) -> Result<(), Self::Error> {
+        self.command(if idle { IDMON } else { IDMOFF }, &[])
+    }
+
+    fn set_partial(&mut self, rows: Option<(u16, u16)>) -> Result<(), Self::Error> {
+        let Some((start, end)) = rows else {
+            return self.command(NORON, &[]);
+        };
+        let (start, end) = (start + self.config.y_offset, end + self.config.y_offset);
+        let [start_hi, start_lo] = start.to_be_bytes();
+        let [end_hi, end_lo] = end.to_be_bytes();
+        self.command(PTLAR, &[start_hi, start_lo, end_hi, end_lo])?;
+        self.command(PTLON, &[])
+    }
+}
+
+/// The MIPI DCS commands, which every DCS controller has at the same place.
+/// Each driver re-exports the ones it has, next to its own commands.
+pub const NOP: u8 = 0x00;
+/// Put every register back to its default. Like a hardware reset, it takes
+/// 5ms from sleep but up to 120ms if the panel was awake, e.g. in
+/// [`Driver::reinit`], so the presets wait the longer one.
+pub const SWRESET: u8 = 0x01;
+pub const RDDID: u8 = 0x04;
+pub const RDDST: u8 = 0x09;
+
+pub const SLPIN: u8 = 0x10;
+pub const SLPOUT: u8 = 0x11;
+pub const PTLON: u8 = 0x12;
+pub const NORON: u8 = 0x13;
+
+pub const INVOFF: u8 = 0x20;
+pub const INVON: u8 = 0x21;
+pub const DISPOFF: u8 = 0x28;
+pub const DISPON: u8 = 0x29;
+
+pub const CASET: u8 = 0x2A;
+pub const RASET: u8 = 0x2B;
+pub const RAMWR: u8 = 0x2C;
+pub const RAMRD: u8 = 0x2E;
+
+pub const PTLAR: u8 = 0x30;
+pub const TEOFF: u8 = 0x34;
+pub const TEON: u8 = 0x35;
+pub const MADCTL: u8 = 0x36;
+pub const IDMOFF: u8 = 0x38;
+pub const IDMON: u8 = 0x39;
+pub const COLMOD: u8 = 0x3A;
+pub const RAMRDC: u8 = 0x3E;
+
+pub const RDID1: u8 = 0xDA;
+pub const RDID2: u8 = 0xDB;
+pub const RDID3: u8 = 0xDC;
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::{
+        interface::mock::{command, driver, Event, Log, DC},
+        panel::PanelConfig,
+        st7789,
+    };
+
+    #[test]
+    fn a_command_comes_after_a_pending_pixel() {
+        let log = RefCell::new(Log::<64>::new());
+        let config = PanelConfig {
+            pixel_format: PixelFormat::Rgb444,
+            ..PanelConfig::P240X240
+        };
+        let mut lcd = driver::<st7789::Chip, 64>(&log, config, st7789::presets::ADAFRUIT);
+        lcd.prepare_window((0, 2), (0, 0)).unwrap();
+        lcd.write_rgb(&[Rgb16::BLACK; 3]).unwrap();
+        log.borrow_mut().clear();
+
+        // the first two pixels have gone, and the third has to go before
+        // `IDMON`, padded out to 2 bytes
+        lcd.set_idle(true).unwrap();
+        let expected = [
+            vec![Event::Pin(DC, true), Event::Write(0xff), Event::Write(0xf0)],
+            command(IDMON, &[]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+}
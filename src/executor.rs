@@ -0,0 +1,54 @@
+//! Just enough async for [`crate::lcd::AsyncLcd`]: a future that lets other
+//! tasks run, and a way to run a future to completion without an executor
+//! (e.g. on the host, or before the real one has started).
+
+use core::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
+
+/// Returns `Pending` once (waking itself straight away), so that whatever
+/// executor we're on can run its other tasks before we carry on
+pub fn yield_now() -> impl Future<Output = ()> {
+    YieldNow { yielded: false }
+}
+
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: core::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Poll `future` until it's done. Nothing else can run in the meantime, so
+/// this just spins, which is fine for futures that only ever
+/// [`yield_now`].
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// A waker that does nothing, since [`block_on`] polls again anyway
+pub(crate) fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+    const RAW: RawWaker = RawWaker::new(core::ptr::null(), &VTABLE);
+    // SAFETY: none of the vtable functions touch the data pointer
+    unsafe { Waker::from_raw(RAW) }
+}
@@ -0,0 +1,119 @@
+/// How many bits each pixel of a glyph takes up. `Mono` is a plain bitmask
+/// (`.bmi`), the others are grayscale coverage levels (`.bmi2`, `.bmi4`)
+/// that we blend between background and foreground.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GlyphFormat {
+    Mono,
+    Gray2,
+    Gray4,
+}
+
+impl GlyphFormat {
+    pub const fn bits_per_pixel(self) -> usize {
+        match self {
+            GlyphFormat::Mono => 1,
+            GlyphFormat::Gray2 => 2,
+            GlyphFormat::Gray4 => 4,
+        }
+    }
+}
+
+/// A fixed-size font, as produced by `procure.py` from a png that is one
+/// glyph wide, with every printable ascii char stacked on top of each other
+/// starting at `' '`. Pixels are packed MSB first with no padding, so glyph
+/// `n` starts at bit `n * width * height * bits_per_pixel`.
+pub struct Font<'a> {
+    pub data: &'a [u8],
+    pub format: GlyphFormat,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The 8x16 font that [`crate::term::Term`] uses
+pub static FONT: Font = Font {
+    data: include_bytes!("./image/font.bmi"),
+    format: GlyphFormat::Mono,
+    width: 8,
+    height: 16,
+};
+
+impl<'a> Font<'a> {
+    /// Get the glyph for an ascii char, anything we don't have a glyph for
+    /// is drawn as a space
+    pub fn glyph(&self, ch: u8) -> Glyph<'_> {
+        let glyph_bits = self.width * self.height * self.format.bits_per_pixel();
+        let glyph_count = self.data.len() * 8 / glyph_bits;
+        let index = match ch.checked_sub(b' ') {
+            Some(index) if (index as usize) < glyph_count => index as usize,
+            _ => 0,
+        };
+
+        Glyph {
+            font: self,
+            start: index * glyph_bits,
+        }
+    }
+}
+
+pub struct Glyph<'a> {
+    font: &'a Font<'a>,
+    /// bit offset into `font.data`
+    start: usize,
+}
+
+impl<'a> Glyph<'a> {
+    /// How much of the pixel at `(col, row)` is covered by the glyph, from
+    /// `0` (background) to `255` (foreground)
+    pub fn coverage(&self, col: usize, row: usize) -> u8 {
+        let bpp = self.font.format.bits_per_pixel();
+        let bit = self.start + (row * self.font.width + col) * bpp;
+        let max = (1 << bpp) - 1;
+        let level = (self.font.data[bit / 8] >> (8 - bpp - bit % 8)) & max;
+
+        (level as u16 * 255 / max as u16) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The coverage of every pixel of the glyph for `ch`, a row at a time
+    fn coverage(font: &Font, ch: u8) -> Vec<Vec<u8>> {
+        let glyph = font.glyph(ch);
+        (0..font.height)
+            .map(|row| {
+                (0..font.width)
+                    .map(|col| glyph.coverage(col, row))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gray2_levels() {
+        // 3x2 glyphs, so `'!'` starts halfway through the second byte
+        let font = Font {
+            data: &[0b00_01_10_11, 0b00_11_11_10, 0b01_00_01_10],
+            format: GlyphFormat::Gray2,
+            width: 3,
+            height: 2,
+        };
+        assert_eq!(coverage(&font, b' '), [[0, 85, 170], [255, 0, 255]]);
+        assert_eq!(coverage(&font, b'!'), [[255, 170, 85], [0, 85, 170]]);
+    }
+
+    #[test]
+    fn gray4_levels() {
+        let font = Font {
+            data: &[0x05, 0xfa, 0x18],
+            format: GlyphFormat::Gray4,
+            width: 3,
+            height: 1,
+        };
+        assert_eq!(coverage(&font, b' '), [[0, 85, 255]]);
+        assert_eq!(coverage(&font, b'!'), [[170, 17, 136]]);
+        // past the end of the font is a space
+        assert_eq!(coverage(&font, b'"'), [[0, 85, 255]]);
+    }
+}
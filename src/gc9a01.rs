@@ -0,0 +1,193 @@
+use crate::{
+    driver::{Controller, DcsPower, Driver},
+    init_sequence::{InitCommand, InitSequence},
+    interface::ResetTiming,
+    lcd::SafeArea,
+};
+
+pub use crate::driver::{
+    CASET, COLMOD, DISPOFF, DISPON, IDMOFF, IDMON, INVOFF, INVON, MADCTL, NOP, NORON, PTLAR, PTLON,
+    RAMWR, RASET, RDDID, RDDST, RDID1, RDID2, RDID3, SLPIN, SLPOUT, SWRESET,
+};
+
+/// A round GC9A01, with [`crate::panel::PanelConfig::GC9A01`], see
+/// [`Driver`]
+pub type Gc9a01<DI, Rst> = Driver<Chip, DI, Rst>;
+
+/// What's different about the GC9A01
+pub enum Chip {}
+
+impl Controller for Chip {
+    const RESET_TIMING: ResetTiming = RESET_TIMING;
+
+    fn safe_area() -> SafeArea {
+        SafeArea::Circle
+    }
+}
+
+impl DcsPower for Chip {
+    const SLPIN_DELAY_MS: u32 = SLPIN_DELAY_MS;
+    const SLPOUT_DELAY_MS: u32 = SLPOUT_DELAY_MS;
+}
+
+pub const RESET_TIMING: ResetTiming = ResetTiming {
+    pulse_us: 10,
+    recovery_ms: 120,
+};
+
+const SWRESET_DELAY_MS: u32 = 120;
+const SLPIN_DELAY_MS: u32 = 5;
+const SLPOUT_DELAY_MS: u32 = 120;
+
+pub const DFUNCTR: u8 = 0xB6;
+
+pub const POWER2: u8 = 0xC3;
+pub const POWER3: u8 = 0xC4;
+pub const POWER4: u8 = 0xC9;
+
+pub const FRAMERATE: u8 = 0xE8;
+
+pub const GAMMA1: u8 = 0xF0;
+pub const GAMMA2: u8 = 0xF1;
+pub const GAMMA3: u8 = 0xF2;
+pub const GAMMA4: u8 = 0xF3;
+
+/// Inter register enable, needed before most of the init sequence
+pub const INREGEN1: u8 = 0xFE;
+pub const INREGEN2: u8 = 0xEF;
+
+/// Init sequences for the GC9A01 modules we've seen
+pub mod presets {
+    use super::*;
+
+    /// The vendor's sequence, by way of Adafruit's GC9A01A library. Most of
+    /// these registers aren't in the datasheet, so they're just numbers.
+    pub const VENDOR: InitSequence = InitSequence::new(&[
+        InitCommand::new(SWRESET, &[]).with_delay(SWRESET_DELAY_MS),
+        InitCommand::new(INREGEN2, &[]),
+        InitCommand::new(0xEB, &[0x14]),
+        InitCommand::new(INREGEN1, &[]),
+        InitCommand::new(INREGEN2, &[]),
+        InitCommand::new(0xEB, &[0x14]),
+        InitCommand::new(0x84, &[0x40]),
+        InitCommand::new(0x85, &[0xff]),
+        InitCommand::new(0x86, &[0xff]),
+        InitCommand::new(0x87, &[0xff]),
+        InitCommand::new(0x88, &[0x0a]),
+        InitCommand::new(0x89, &[0x21]),
+        InitCommand::new(0x8A, &[0x00]),
+        InitCommand::new(0x8B, &[0x80]),
+        InitCommand::new(0x8C, &[0x01]),
+        InitCommand::new(0x8D, &[0x01]),
+        InitCommand::new(0x8E, &[0xff]),
+        InitCommand::new(0x8F, &[0xff]),
+        InitCommand::new(DFUNCTR, &[0x00, 0x00]),
+        InitCommand::new(COLMOD, &[0x05]),
+        InitCommand::new(0x90, &[0x08, 0x08, 0x08, 0x08]),
+        InitCommand::new(0xBD, &[0x06]),
+        InitCommand::new(0xBC, &[0x00]),
+        InitCommand::new(0xFF, &[0x60, 0x01, 0x04]),
+        // power control
+        InitCommand::new(POWER2, &[0x13]),
+        InitCommand::new(POWER3, &[0x13]),
+        InitCommand::new(POWER4, &[0x22]),
+        InitCommand::new(0xBE, &[0x11]),
+        InitCommand::new(0xE1, &[0x10, 0x0e]),
+        InitCommand::new(0xDF, &[0x21, 0x0c, 0x02]),
+        // set gamma
+        InitCommand::new(GAMMA1, &[0x45, 0x09, 0x08, 0x08, 0x26, 0x2a]),
+        InitCommand::new(GAMMA2, &[0x43, 0x70, 0x72, 0x36, 0x37, 0x6f]),
+        InitCommand::new(GAMMA3, &[0x45, 0x09, 0x08, 0x08, 0x26, 0x2a]),
+        InitCommand::new(GAMMA4, &[0x43, 0x70, 0x72, 0x36, 0x37, 0x6f]),
+        InitCommand::new(0xED, &[0x1b, 0x0b]),
+        InitCommand::new(0xAE, &[0x77]),
+        InitCommand::new(0xCD, &[0x63]),
+        InitCommand::new(FRAMERATE, &[0x34]),
+        InitCommand::new(
+            0x62,
+            &[
+                0x18, 0x0d, 0x71, 0xed, //
+                0x70, 0x70, 0x18, 0x0f, //
+                0x71, 0xef, 0x70, 0x70,
+            ],
+        ),
+        InitCommand::new(
+            0x63,
+            &[
+                0x18, 0x11, 0x71, 0xf1, //
+                0x70, 0x70, 0x18, 0x13, //
+                0x71, 0xf3, 0x70, 0x70,
+            ],
+        ),
+        InitCommand::new(0x64, &[0x28, 0x29, 0xf1, 0x01, 0xf1, 0x00, 0x07]),
+        InitCommand::new(
+            0x66,
+            &[
+                0x3c, 0x00, 0xcd, 0x67, //
+                0x45, 0x45, 0x10, 0x00, //
+                0x00, 0x00,
+            ],
+        ),
+        InitCommand::new(
+            0x67,
+            &[
+                0x00, 0x3c, 0x00, 0x00, //
+                0x00, 0x01, 0x54, 0x10, //
+                0x32, 0x98,
+            ],
+        ),
+        InitCommand::new(0x74, &[0x10, 0x85, 0x80, 0x00, 0x00, 0x4e, 0x00]),
+        InitCommand::new(0x98, &[0x3e, 0x07]),
+        InitCommand::new(SLPOUT, &[]).with_delay(SLPOUT_DELAY_MS),
+        InitCommand::new(DISPON, &[]).with_delay(20),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::{
+        interface::mock::{command, driver, sequence, Event, Log, NoDelay, RST},
+        lcd::Lcd,
+        panel::PanelConfig,
+        term::Term,
+    };
+
+    #[test]
+    fn init() {
+        let log = RefCell::new(Log::<1024>::new());
+        let mut lcd: Gc9a01<_, _> = driver(&log, PanelConfig::GC9A01, presets::VENDOR);
+        lcd.init(&mut NoDelay).unwrap();
+
+        let expected: Vec<Event> = [
+            vec![Event::Pin(RST, false), Event::Pin(RST, true)],
+            sequence(&presets::VENDOR),
+            // mirrored and BGR
+            command(0x36, &[0x48]),
+            command(0x3a, &[0x55]),
+            command(0x20, &[]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn only_cells_inside_the_circle_are_visible() {
+        let log = RefCell::new(Log::<16>::new());
+        let lcd: Gc9a01<_, _> = driver(&log, PanelConfig::GC9A01, presets::VENDOR);
+        assert_eq!(lcd.safe_area(), SafeArea::Circle);
+
+        let term = Term::<30, 15>::new();
+        assert!(!term.is_cell_visible(&lcd, (0, 0)));
+        assert!(term.is_cell_visible(&lcd, (14, 0)));
+        assert!(term.is_cell_visible(&lcd, (0, 7)));
+        assert!(term.is_cell_visible(&lcd, (29, 7)));
+        assert_eq!(term.visible_columns(&lcd, 0), 14..16);
+        assert_eq!(term.visible_columns(&lcd, 1), 8..22);
+        assert_eq!(term.visible_columns(&lcd, 7), 0..30);
+        assert_eq!(term.visible_columns(&lcd, 13), 8..22);
+        assert_eq!(term.visible_columns(&lcd, 14), 14..16);
+    }
+}
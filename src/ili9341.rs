@@ -0,0 +1,180 @@
+use crate::{
+    driver::{Controller, DcsPower, Driver},
+    init_sequence::{InitCommand, InitSequence},
+    interface::ResetTiming,
+    pixel_format::PixelFormat,
+};
+
+// the datasheet calls `RASET` `PASET`, and `COLMOD` `PIXFMT`
+pub use crate::driver::{
+    CASET, COLMOD, DISPOFF, DISPON, IDMOFF, IDMON, INVOFF, INVON, MADCTL, NOP, NORON, PTLAR, PTLON,
+    RAMRD, RAMWR, RASET, RDDID, RDDST, RDID1, RDID2, RDID3, SLPIN, SLPOUT, SWRESET,
+};
+
+/// An ILI9341, usually with [`crate::panel::PanelConfig::ILI9341`], see
+/// [`Driver`]
+pub type Ili9341<DI, Rst> = Driver<Chip, DI, Rst>;
+
+/// What's different about the ILI9341
+pub enum Chip {}
+
+impl Controller for Chip {
+    const RESET_TIMING: ResetTiming = RESET_TIMING;
+    /// There's no RGB444 over the MCU interface
+    const PIXEL_FORMATS: &'static [PixelFormat] = &[PixelFormat::Rgb565, PixelFormat::Rgb666];
+}
+
+impl DcsPower for Chip {
+    const SLPIN_DELAY_MS: u32 = SLPIN_DELAY_MS;
+    const SLPOUT_DELAY_MS: u32 = SLPOUT_DELAY_MS;
+}
+
+pub const RESET_TIMING: ResetTiming = ResetTiming {
+    pulse_us: 10,
+    recovery_ms: 120,
+};
+
+const SWRESET_DELAY_MS: u32 = 120;
+const SLPIN_DELAY_MS: u32 = 5;
+const SLPOUT_DELAY_MS: u32 = 5;
+
+pub const GAMMASET: u8 = 0x26;
+
+pub const VSCRSADD: u8 = 0x37;
+
+pub const FRMCTR1: u8 = 0xB1;
+pub const FRMCTR2: u8 = 0xB2;
+pub const FRMCTR3: u8 = 0xB3;
+pub const INVCTR: u8 = 0xB4;
+pub const DFUNCTR: u8 = 0xB6;
+
+pub const PWCTR1: u8 = 0xC0;
+pub const PWCTR2: u8 = 0xC1;
+pub const VMCTR1: u8 = 0xC5;
+pub const VMCTR2: u8 = 0xC7;
+
+pub const PWCTRA: u8 = 0xCB;
+pub const PWCTRB: u8 = 0xCF;
+
+pub const RDID4: u8 = 0xDD;
+
+pub const GMCTRP1: u8 = 0xE0;
+pub const GMCTRN1: u8 = 0xE1;
+
+pub const DTCA: u8 = 0xE8;
+pub const DTCB: u8 = 0xEA;
+pub const PWRSEQ: u8 = 0xED;
+pub const ENABLE3G: u8 = 0xF2;
+pub const PRC: u8 = 0xF7;
+
+/// Init sequences for the ILI9341 modules we've seen
+pub mod presets {
+    use super::*;
+
+    /// From Adafruit's ILI9341 library, which most cheap 2.4"/2.8" modules
+    /// seem to be happy with
+    pub const ADAFRUIT: InitSequence = InitSequence::new(&[
+        InitCommand::new(SWRESET, &[]).with_delay(SWRESET_DELAY_MS),
+        // undocumented, but everyone sends it
+        InitCommand::new(0xEF, &[0x03, 0x80, 0x02]),
+        InitCommand::new(PWCTRB, &[0x00, 0xc1, 0x30]),
+        InitCommand::new(PWRSEQ, &[0x64, 0x03, 0x12, 0x81]),
+        InitCommand::new(DTCA, &[0x85, 0x00, 0x78]),
+        InitCommand::new(PWCTRA, &[0x39, 0x2c, 0x00, 0x34, 0x02]),
+        InitCommand::new(PRC, &[0x20]),
+        InitCommand::new(DTCB, &[0x00, 0x00]),
+        // power and VCOM control
+        InitCommand::new(PWCTR1, &[0x23]),
+        InitCommand::new(PWCTR2, &[0x10]),
+        InitCommand::new(VMCTR1, &[0x3e, 0x28]),
+        InitCommand::new(VMCTR2, &[0x86]),
+        InitCommand::new(VSCRSADD, &[0x00]),
+        InitCommand::new(COLMOD, &[0x55]),
+        InitCommand::new(FRMCTR1, &[0x00, 0x18]),
+        InitCommand::new(DFUNCTR, &[0x08, 0x82, 0x27]),
+        InitCommand::new(ENABLE3G, &[0x00]),
+        InitCommand::new(GAMMASET, &[0x01]),
+        InitCommand::new(
+            GMCTRP1,
+            &[
+                0x0f, 0x31, 0x2b, 0x0c, //
+                0x0e, 0x08, 0x4e, 0xf1, //
+                0x37, 0x07, 0x10, 0x03, //
+                0x0e, 0x09, 0x00,
+            ],
+        ),
+        InitCommand::new(
+            GMCTRN1,
+            &[
+                0x00, 0x0e, 0x14, 0x03, //
+                0x11, 0x07, 0x31, 0xc1, //
+                0x48, 0x08, 0x0f, 0x0c, //
+                0x31, 0x36, 0x0f,
+            ],
+        ),
+        InitCommand::new(SLPOUT, &[]).with_delay(SLPOUT_DELAY_MS),
+        InitCommand::new(DISPON, &[]),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::{
+        interface::{
+            mock::{command, driver, sequence, Event, Log, NoDelay, RST},
+            Error,
+        },
+        panel::{PanelConfig, Rotation},
+    };
+
+    #[test]
+    fn init() {
+        let log = RefCell::new(Log::<512>::new());
+        let mut lcd: Ili9341<_, _> = driver(&log, PanelConfig::ILI9341, presets::ADAFRUIT);
+        lcd.init(&mut NoDelay).unwrap();
+
+        let expected: Vec<Event> = [
+            vec![Event::Pin(RST, false), Event::Pin(RST, true)],
+            sequence(&presets::ADAFRUIT),
+            // mirrored and BGR
+            command(0x36, &[0x48]),
+            command(0x3a, &[0x55]),
+            command(0x21, &[]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn set_window_in_landscape() {
+        let log = RefCell::new(Log::<32>::new());
+        let config = PanelConfig {
+            rotation: Rotation::Deg90,
+            ..PanelConfig::ILI9341
+        };
+        let mut lcd: Ili9341<_, _> = driver(&log, config, presets::ADAFRUIT);
+        lcd.set_window((0, 319), (0, 239)).unwrap();
+
+        let expected = [
+            command(0x2a, &[0x00, 0x00, 0x01, 0x3f]),
+            command(0x2b, &[0x00, 0x00, 0x00, 0xef]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn rgb444_is_unsupported() {
+        let log = RefCell::new(Log::<16>::new());
+        let config = PanelConfig {
+            pixel_format: PixelFormat::Rgb444,
+            ..PanelConfig::ILI9341
+        };
+        let mut lcd: Ili9341<_, _> = driver(&log, config, presets::ADAFRUIT);
+        assert!(matches!(lcd.init(&mut NoDelay), Err(Error::Unsupported)));
+        assert_eq!(log.borrow().events(), []);
+    }
+}
@@ -0,0 +1,263 @@
+use core::{fmt::Debug, ops::Range};
+
+use crate::lcd::{Lcd, Rgb16};
+
+pub mod qoi;
+pub mod rle;
+
+/// Include a `.rgb565` file (as produced by `procure.py`) as a `&[Rgb16]`
+#[macro_export]
+macro_rules! include_rgb565 {
+    ($path:expr) => {{
+        let bytes = include_bytes!($path);
+        $crate::lcd::Rgb16::from_bytes(bytes)
+    }};
+}
+
+pub use include_rgb565;
+
+/// Drawing an encoded image went wrong
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error<LcdE> {
+    Lcd(LcdE),
+    /// The data ended before all of the pixels we needed
+    Truncated,
+}
+
+/// The data of an [`rle::RleImage`] or a [`qoi::QoiImage`] ended in the
+/// middle of the image
+#[derive(Debug, PartialEq, Eq)]
+pub struct Truncated;
+
+impl<LcdE> From<Truncated> for Error<LcdE> {
+    fn from(Truncated: Truncated) -> Self {
+        Error::Truncated
+    }
+}
+
+/// A raw RGB565 image, stored row by row. Usually made with
+/// ```no_run
+/// const LOGO: Image = Image::new(50, 50, include_rgb565!("./image/test.rgb565"));
+/// ```
+#[derive(Clone, Copy)]
+pub struct Image<'a> {
+    pub width: u16,
+    pub height: u16,
+    pub pixels: &'a [Rgb16],
+}
+
+impl<'a> Image<'a> {
+    /// Panics if `pixels` is not exactly `width * height` long
+    pub const fn new(width: u16, height: u16, pixels: &'a [Rgb16]) -> Self {
+        assert!(pixels.len() == width as usize * height as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Draw this image with its top-left corner at `(x, y)`. Anything that
+    /// falls outside of the display is clipped, so `x` and `y` can be
+    /// negative.
+    pub fn draw<L: Lcd>(&self, lcd: &mut L, pos: (i32, i32)) -> Result<(), L::Error> {
+        let Some((cols, rows)) = prepare_visible(lcd, pos, (self.width, self.height))? else {
+            return Ok(());
+        };
+
+        // every visible row is contiguous in `pixels`, so we can just write
+        // slices of it without copying anything
+        let width = self.width as usize;
+        for row in rows {
+            let start = row * width;
+            lcd.write_rgb(&self.pixels[start + cols.start..start + cols.end])?;
+        }
+        lcd.flush()
+    }
+}
+
+/// Work out which columns and rows of a `(width, height)` image at `(x, y)`
+/// are actually on the display, and prepare a window covering just those.
+/// Returns `None` (without touching the display) if nothing is visible.
+#[allow(clippy::type_complexity)]
+fn prepare_visible<L: Lcd>(
+    lcd: &mut L,
+    (x, y): (i32, i32),
+    (width, height): (u16, u16),
+) -> Result<Option<(Range<usize>, Range<usize>)>, L::Error> {
+    let (lcd_width, lcd_height) = lcd.dimensions();
+    let cols = (-x).max(0)..(lcd_width as i32 - x).min(width as i32);
+    let rows = (-y).max(0)..(lcd_height as i32 - y).min(height as i32);
+    if cols.is_empty() || rows.is_empty() {
+        return Ok(None);
+    }
+
+    lcd.prepare_window(
+        ((x + cols.start) as u16, (x + cols.end - 1) as u16),
+        ((y + rows.start) as u16, (y + rows.end - 1) as u16),
+    )?;
+    Ok(Some((
+        cols.start as usize..cols.end as usize,
+        rows.start as usize..rows.end as usize,
+    )))
+}
+
+/// Draw an image that is decoded as a stream of `(colour, count)` runs,
+/// covering the image row by row. Like [`Image::draw`], anything off the
+/// display is clipped -- we still have to decode it though.
+fn draw_runs<L: Lcd>(
+    lcd: &mut L,
+    pos: (i32, i32),
+    (width, height): (u16, u16),
+    runs: impl IntoIterator<Item = Result<(Rgb16, usize), Truncated>>,
+) -> Result<(), Error<L::Error>> {
+    let Some((cols, rows)) = prepare_visible(lcd, pos, (width, height)).map_err(Error::Lcd)? else {
+        return Ok(());
+    };
+
+    let mut buf = [Rgb16::BLACK; 64];
+    let mut len = 0;
+    let (mut col, mut row) = (0, 0);
+    for run in runs {
+        let (colour, count) = run?;
+        for _ in 0..count {
+            if rows.contains(&row) && cols.contains(&col) {
+                buf[len] = colour;
+                len += 1;
+                if len == buf.len() {
+                    lcd.write_rgb(&buf).map_err(Error::Lcd)?;
+                    len = 0;
+                }
+            }
+
+            col += 1;
+            if col == width as usize {
+                col = 0;
+                row += 1;
+            }
+        }
+
+        // no point decoding anything after the last visible row
+        if row >= rows.end {
+            break;
+        }
+    }
+    // the runs ran out before the window did
+    if row < rows.end {
+        return Err(Error::Truncated);
+    }
+
+    if len != 0 {
+        lcd.write_rgb(&buf[..len]).map_err(Error::Lcd)?;
+    }
+    lcd.flush().map_err(Error::Lcd)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::convert::Infallible;
+
+    use super::{rle::RleImage, *};
+
+    /// An [`Lcd`] that keeps what was drawn where, `None` if nothing was
+    struct Canvas {
+        pixels: [[Option<Rgb16>; 5]; 3],
+        window: ((u16, u16), (u16, u16)),
+        written: usize,
+    }
+
+    impl Canvas {
+        fn new() -> Self {
+            Self {
+                pixels: [[None; 5]; 3],
+                window: ((0, 0), (0, 0)),
+                written: 0,
+            }
+        }
+    }
+
+    impl Lcd for Canvas {
+        type Error = Infallible;
+
+        fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error> {
+            self.window = (x, y);
+            self.written = 0;
+            Ok(())
+        }
+
+        fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error> {
+            let ((x0, x1), (y0, y1)) = self.window;
+            let width = (x1 - x0 + 1) as usize;
+            for &pixel in rgb {
+                let (x, y) = (
+                    x0 as usize + self.written % width,
+                    y0 as usize + self.written / width,
+                );
+                assert!(y <= y1 as usize, "wrote past the end of the window");
+                self.pixels[y][x] = Some(pixel);
+                self.written += 1;
+            }
+            Ok(())
+        }
+
+        fn dimensions(&self) -> (u16, u16) {
+            (5, 3)
+        }
+    }
+
+    /// The same 4x3 image, raw and run-length encoded
+    fn images() -> (Vec<Rgb16>, Vec<u8>) {
+        let [a, b, c] =
+            [(255, 0, 0), (0, 255, 0), (0, 0, 255)].map(|(r, g, b)| Rgb16::from_rgb(r, g, b));
+        let raw = vec![a, a, a, a, a, b, c, a, c, c, c, c];
+        let mut rle = vec![0x80 | 4];
+        rle.extend_from_slice(Rgb16::as_bytes(&[a]));
+        rle.push(2);
+        rle.extend_from_slice(Rgb16::as_bytes(&[b, c, a]));
+        rle.push(0x80 | 3);
+        rle.extend_from_slice(Rgb16::as_bytes(&[c]));
+        (raw, rle)
+    }
+
+    #[test]
+    fn runs_are_clipped_like_an_image() {
+        let (raw, rle) = images();
+        let image = Image::new(4, 3, &raw);
+        let encoded = RleImage::new(4, 3, &rle);
+        for x in -5..=6 {
+            for y in -4..=4 {
+                let mut expected = Canvas::new();
+                image.draw(&mut expected, (x, y)).unwrap();
+                let mut canvas = Canvas::new();
+                encoded.draw(&mut canvas, (x, y)).unwrap();
+                assert_eq!(canvas.pixels, expected.pixels, "at ({x}, {y})");
+            }
+        }
+
+        // the bottom-right corner of the image, in the top-left corner of
+        // the display
+        let mut canvas = Canvas::new();
+        encoded.draw(&mut canvas, (-2, -1)).unwrap();
+        assert_eq!(canvas.pixels[0][..3], [Some(raw[6]), Some(raw[7]), None]);
+        assert_eq!(canvas.pixels[1][..3], [Some(raw[10]), Some(raw[11]), None]);
+        assert_eq!(canvas.pixels[2], [None; 5]);
+    }
+
+    #[test]
+    fn truncated_runs_are_an_error() {
+        let (_, rle) = images();
+        // missing the last run, so the bottom row
+        let encoded = RleImage::new(4, 3, &rle[..rle.len() - 3]);
+        assert_eq!(
+            encoded.draw(&mut Canvas::new(), (0, 0)),
+            Err(Error::Truncated)
+        );
+        assert_eq!(
+            encoded.draw(&mut Canvas::new(), (0, -1)),
+            Err(Error::Truncated)
+        );
+        // which is fine if that row isn't on the display
+        assert_eq!(encoded.draw(&mut Canvas::new(), (0, 1)), Ok(()));
+        assert_eq!(encoded.draw(&mut Canvas::new(), (5, 0)), Ok(()));
+    }
+}
@@ -1,3 +1,4 @@
+import argparse
 import os
 from PIL import Image
 
@@ -6,19 +7,88 @@ IMAGE_DIR = os.path.dirname(os.path.realpath(__file__))
 all_files = os.listdir(IMAGE_DIR)
 PNG_FILES = [f"{IMAGE_DIR}/{file}" for file in all_files if file.endswith(".png")]
 
+# thresholds in 0..16, must match `Rgb16::from_rgb_dithered`
+BAYER = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+]
 
-def to_rgb16(rgba: tuple[int, int, int, int]) -> tuple[int, int]:
-    r, g, b, _ = rgba
-    r_565 = r >> 3
-    g_565 = g >> 2
-    b_565 = b >> 3
 
+def pack_rgb16(r_565: int, g_565: int, b_565: int) -> tuple[int, int]:
     inverted = (r_565 << 11) | (g_565 << 5) | b_565
     rgb = inverted ^ 0xFFFF
 
     return ((rgb >> 8) % 255, rgb & 0xFF)
 
 
+def to_rgb16(rgba: tuple[int, int, int, int]) -> tuple[int, int]:
+    r, g, b, _ = rgba
+    return pack_rgb16(r >> 3, g >> 2, b >> 3)
+
+
+def to_rgb16_bayer(
+    x: int, y: int, rgba: tuple[int, int, int, int]
+) -> tuple[int, int]:
+    r, g, b, a = rgba
+    t = BAYER[y % 4][x % 4]
+    return to_rgb16(
+        (min(r + t // 2, 255), min(g + t // 4, 255), min(b + t // 2, 255), a)
+    )
+
+
+def to_rgb16_floyd_steinberg(
+    rgba_vals: list[tuple[int, int, int, int]], width: int, height: int
+) -> list[tuple[int, int]]:
+    """
+    Quantize row by row, pushing the error of each pixel onto its neighbours
+    that we haven't quantized yet
+    """
+    channels = [[float(val[c]) for val in rgba_vals] for c in range(3)]
+    # bits kept for r, g and b
+    bits = [5, 6, 5]
+
+    quantized = [[0] * len(rgba_vals) for _ in range(3)]
+    for y in range(height):
+        for x in range(width):
+            i = y * width + x
+            for c in range(3):
+                old = min(max(round(channels[c][i]), 0), 255)
+                q = old >> (8 - bits[c])
+                quantized[c][i] = q
+                # what the panel will actually show for `q`
+                shown = (q << (8 - bits[c])) | (q >> (2 * bits[c] - 8))
+                err = channels[c][i] - shown
+
+                if x + 1 < width:
+                    channels[c][i + 1] += err * 7 / 16
+                if y + 1 < height:
+                    if x > 0:
+                        channels[c][i + width - 1] += err * 3 / 16
+                    channels[c][i + width] += err * 5 / 16
+                    if x + 1 < width:
+                        channels[c][i + width + 1] += err * 1 / 16
+
+    return [
+        pack_rgb16(quantized[0][i], quantized[1][i], quantized[2][i])
+        for i in range(len(rgba_vals))
+    ]
+
+
+def convert_rgb16(
+    rgba_vals: list[tuple[int, int, int, int]], width: int, height: int, dither: str
+) -> list[tuple[int, int]]:
+    if dither == "bayer":
+        return [
+            to_rgb16_bayer(i % width, i // width, val)
+            for i, val in enumerate(rgba_vals)
+        ]
+    if dither == "floyd-steinberg":
+        return to_rgb16_floyd_steinberg(rgba_vals, width, height)
+    return [to_rgb16(val) for val in rgba_vals]
+
+
 def as_byte(n: int) -> bytes:
     return n.to_bytes(byteorder="little", length=1)
 
@@ -32,6 +102,164 @@ def byte_from_bits(bits: list[bool]) -> bytes:
     return as_byte(n)
 
 
+def pack_levels(levels: list[int], bpp: int) -> bytes:
+    """
+    Pack `bpp`-bit values MSB first with no padding (other than at the very
+    end), see `font.rs`
+    """
+    out = bytearray()
+    acc = 0
+    n_bits = 0
+    for level in levels:
+        acc = (acc << bpp) | level
+        n_bits += bpp
+        if n_bits == 8:
+            out.append(acc)
+            acc = 0
+            n_bits = 0
+    if n_bits != 0:
+        out.append(acc << (8 - n_bits))
+    return bytes(out)
+
+
+def glyph_advances(
+    rgba_vals: list[tuple[int, int, int, int]], width: int, glyph_height: int
+) -> bytes:
+    """
+    For a font png (one glyph wide, glyphs stacked vertically), how far to
+    advance after each glyph: just past its rightmost pixel, plus a pixel of
+    spacing. Empty glyphs (space) get half the width. See `text.rs`.
+    """
+    glyph_count = len(rgba_vals) // (width * glyph_height)
+    advances = bytearray()
+    for glyph in range(glyph_count):
+        start = glyph * width * glyph_height
+        used_cols = [
+            x
+            for x in range(width)
+            for y in range(glyph_height)
+            if rgba_vals[start + y * width + x][3] != 0
+        ]
+        advances.append(max(used_cols) + 2 if used_cols else width // 2)
+    return bytes(advances)
+
+
+def rle_encode(rgb16_vals: list[tuple[int, int]]) -> bytes:
+    """
+    Packets are a header byte `h` followed by pixels. If `h & 0x80`, the next
+    pixel is repeated `(h & 0x7f) + 1` times, otherwise `h + 1` literal pixels
+    follow. See `rle.rs`.
+    """
+    out = bytearray()
+    literals: list[tuple[int, int]] = []
+
+    def flush_literals():
+        for i in range(0, len(literals), 128):
+            chunk = literals[i : i + 128]
+            out.append(len(chunk) - 1)
+            for msb, lsb in chunk:
+                out.extend((msb, lsb))
+        literals.clear()
+
+    i = 0
+    while i < len(rgb16_vals):
+        run = 1
+        while (
+            i + run < len(rgb16_vals)
+            and run < 128
+            and rgb16_vals[i + run] == rgb16_vals[i]
+        ):
+            run += 1
+
+        # a run of 2 costs the same as 2 literals, and breaks up literal packets
+        if run > 2:
+            flush_literals()
+            out.append(0x80 | (run - 1))
+            out.extend(rgb16_vals[i])
+        else:
+            literals.extend(rgb16_vals[i : i + run])
+        i += run
+
+    flush_literals()
+    return bytes(out)
+
+
+def qoi_encode(
+    rgba_vals: list[tuple[int, int, int, int]], width: int, height: int
+) -> bytes:
+    """
+    Standard QOI (https://qoiformat.org/qoi-specification.pdf), see `qoi.rs`
+    """
+
+    def qoi_hash(px: tuple[int, int, int, int]) -> int:
+        r, g, b, a = px
+        return (r * 3 + g * 5 + b * 7 + a * 11) % 64
+
+    def wrapping_diff(a: int, b: int) -> int:
+        return ((a - b + 128) & 0xFF) - 128
+
+    out = bytearray(b"qoif")
+    out += width.to_bytes(4, "big") + height.to_bytes(4, "big")
+    out += bytes([4, 0])  # RGBA, sRGB
+
+    index = [(0, 0, 0, 0)] * 64
+    prev = (0, 0, 0, 255)
+    run = 0
+    for i, px in enumerate(rgba_vals):
+        if px == prev:
+            run += 1
+            if run == 62 or i == len(rgba_vals) - 1:
+                out.append(0xC0 | (run - 1))
+                run = 0
+            continue
+
+        if run > 0:
+            out.append(0xC0 | (run - 1))
+            run = 0
+
+        h = qoi_hash(px)
+        if index[h] == px:
+            out.append(h)
+        else:
+            index[h] = px
+            r, g, b, a = px
+            if a == prev[3]:
+                dr = wrapping_diff(r, prev[0])
+                dg = wrapping_diff(g, prev[1])
+                db = wrapping_diff(b, prev[2])
+                dr_dg = dr - dg
+                db_dg = db - dg
+                if -2 <= dr <= 1 and -2 <= dg <= 1 and -2 <= db <= 1:
+                    out.append(0x40 | (dr + 2) << 4 | (dg + 2) << 2 | (db + 2))
+                elif -32 <= dg <= 31 and -8 <= dr_dg <= 7 and -8 <= db_dg <= 7:
+                    out.append(0x80 | (dg + 32))
+                    out.append((dr_dg + 8) << 4 | (db_dg + 8))
+                else:
+                    out += bytes([0xFE, r, g, b])
+            else:
+                out += bytes([0xFF, r, g, b, a])
+        prev = px
+
+    out += bytes([0] * 7 + [1])
+    return bytes(out)
+
+
+parser = argparse.ArgumentParser(
+    description="Convert every .png in this directory to .rgb565, .rle565, .qoi, .bmi*"
+)
+parser.add_argument(
+    "--dither",
+    choices=["none", "bayer", "floyd-steinberg"],
+    default="none",
+    help="how to dither when truncating to RGB565 (QOI keeps the full colour)",
+)
+parser.add_argument(
+    "--glyph-height",
+    type=int,
+    help="treat pngs as fonts with glyphs this tall, and write their .adv widths",
+)
+ARGS = parser.parse_args()
+
 for f in PNG_FILES:
     with Image.open(f) as img:
         img = img.convert("RGBA")
@@ -42,7 +270,7 @@ for f in PNG_FILES:
                 rgba: tuple[int, int, int, int] = img.getpixel((x, y))
                 rgba_vals.append(rgba)
 
-        rgb16_vals = [to_rgb16(val) for val in rgba_vals]
+        rgb16_vals = convert_rgb16(rgba_vals, img.width, img.height, ARGS.dither)
 
         rgb565_filename = f[:-4] + ".rgb565"
         with open(rgb565_filename, "wb") as f2:
@@ -51,6 +279,16 @@ for f in PNG_FILES:
                 f2.write(as_byte(lsb))
             print(f"+ {rgb565_filename}")
 
+        rle565_filename = f[:-4] + ".rle565"
+        with open(rle565_filename, "wb") as f2:
+            f2.write(rle_encode(rgb16_vals))
+            print(f"+ {rle565_filename}")
+
+        qoi_filename = f[:-4] + ".qoi"
+        with open(qoi_filename, "wb") as f2:
+            f2.write(qoi_encode(rgba_vals, img.width, img.height))
+            print(f"+ {qoi_filename}")
+
         # now for the bitmask image
 
         bmi_bits = [val[3] != 0 for val in rgba_vals]
@@ -66,3 +304,19 @@ for f in PNG_FILES:
             for byte in bmi_bytes:
                 f2.write(byte)
             print(f"+ {bmi_filename}")
+
+        # and grayscale versions of the bitmask, for anti-aliased fonts
+
+        for bpp in (2, 4):
+            max_level = (1 << bpp) - 1
+            levels = [round(val[3] * max_level / 255) for val in rgba_vals]
+            gray_filename = f"{f[:-4]}.bmi{bpp}"
+            with open(gray_filename, "wb") as f2:
+                f2.write(pack_levels(levels, bpp))
+                print(f"+ {gray_filename}")
+
+        if ARGS.glyph_height is not None and img.height % ARGS.glyph_height == 0:
+            adv_filename = f[:-4] + ".adv"
+            with open(adv_filename, "wb") as f2:
+                f2.write(glyph_advances(rgba_vals, img.width, ARGS.glyph_height))
+                print(f"+ {adv_filename}")
@@ -0,0 +1,204 @@
+//! A decoder for the [QOI format](https://qoiformat.org/qoi-specification.pdf).
+//! QOI is a lot better than RLE for anything that isn't flat colour, and the
+//! decoder only needs 256 bytes of state. Alpha is decoded, but ignored when
+//! we convert to [`Rgb16`].
+
+use super::{Error, Truncated};
+use crate::lcd::{Lcd, Rgb16};
+
+const HEADER_LEN: usize = 14;
+
+const OP_INDEX: u8 = 0b0000_0000;
+const OP_DIFF: u8 = 0b0100_0000;
+const OP_LUMA: u8 = 0b1000_0000;
+const OP_RUN: u8 = 0b1100_0000;
+const OP_RGB: u8 = 0xfe;
+const OP_RGBA: u8 = 0xff;
+const MASK_2: u8 = 0b1100_0000;
+
+#[derive(Clone, Copy)]
+pub struct QoiImage<'a> {
+    pub width: u16,
+    pub height: u16,
+    /// everything after the header
+    data: &'a [u8],
+}
+
+impl<'a> QoiImage<'a> {
+    /// Parse the header of a `.qoi` file, e.g.
+    /// ```no_run
+    /// const SPLASH: QoiImage = QoiImage::new(include_bytes!("./image/splash.qoi"));
+    /// ```
+    /// Panics if this isn't a QOI image, or if it is too big to ever fit on
+    /// a display.
+    pub const fn new(bytes: &'a [u8]) -> Self {
+        assert!(bytes.len() >= HEADER_LEN, "QOI image is missing its header");
+        assert!(
+            bytes[0] == b'q' && bytes[1] == b'o' && bytes[2] == b'i' && bytes[3] == b'f',
+            "QOI image has the wrong magic bytes"
+        );
+        let width = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let height = u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        assert!(width <= u16::MAX as u32 && height <= u16::MAX as u32);
+
+        Self {
+            width: width as u16,
+            height: height as u16,
+            data: bytes.split_at(HEADER_LEN).1,
+        }
+    }
+
+    pub fn runs(&self) -> Runs<'a> {
+        Runs {
+            data: self.data,
+            index: [[0; 4]; 64],
+            pixel: [0, 0, 0, 255],
+            remaining: self.width as usize * self.height as usize,
+        }
+    }
+
+    /// Decode this image straight onto the display, see [`super::Image::draw`]
+    pub fn draw<L: Lcd>(&self, lcd: &mut L, pos: (i32, i32)) -> Result<(), Error<L::Error>> {
+        super::draw_runs(lcd, pos, (self.width, self.height), self.runs())
+    }
+}
+
+/// Iterator over the `(colour, count)` runs of a [`QoiImage`]. If the data
+/// ends before every pixel has been decoded, this gives [`Truncated`] and
+/// then stops.
+pub struct Runs<'a> {
+    data: &'a [u8],
+    /// previously seen pixels, as RGBA
+    index: [[u8; 4]; 64],
+    pixel: [u8; 4],
+    /// pixels left to decode -- we stop here rather than at the end marker
+    remaining: usize,
+}
+
+impl<'a> Runs<'a> {
+    fn next_byte(&mut self) -> Result<u8, Truncated> {
+        let (&byte, rest) = self.data.split_first().ok_or(Truncated)?;
+        self.data = rest;
+        Ok(byte)
+    }
+
+    fn hash([r, g, b, a]: [u8; 4]) -> usize {
+        (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+    }
+
+    fn next_run(&mut self) -> Result<(Rgb16, usize), Truncated> {
+        let mut count = 1;
+        let [mut r, mut g, mut b, mut a] = self.pixel;
+        let op = self.next_byte()?;
+        match op {
+            OP_RGB => {
+                r = self.next_byte()?;
+                g = self.next_byte()?;
+                b = self.next_byte()?;
+            }
+            OP_RGBA => {
+                r = self.next_byte()?;
+                g = self.next_byte()?;
+                b = self.next_byte()?;
+                a = self.next_byte()?;
+            }
+            _ => match op & MASK_2 {
+                OP_INDEX => [r, g, b, a] = self.index[op as usize],
+                OP_DIFF => {
+                    r = r.wrapping_add((op >> 4) & 0b11).wrapping_sub(2);
+                    g = g.wrapping_add((op >> 2) & 0b11).wrapping_sub(2);
+                    b = b.wrapping_add(op & 0b11).wrapping_sub(2);
+                }
+                OP_LUMA => {
+                    let dg = (op & 0b0011_1111).wrapping_sub(32);
+                    let next = self.next_byte()?;
+                    r = r.wrapping_add(dg.wrapping_add(next >> 4).wrapping_sub(8));
+                    g = g.wrapping_add(dg);
+                    b = b.wrapping_add(dg.wrapping_add(next & 0b1111).wrapping_sub(8));
+                }
+                OP_RUN => count = (op & 0b0011_1111) as usize + 1,
+                _ => unreachable!(),
+            },
+        }
+        self.pixel = [r, g, b, a];
+
+        self.index[Self::hash(self.pixel)] = self.pixel;
+        let count = count.min(self.remaining);
+        self.remaining -= count;
+
+        let [r, g, b, _] = self.pixel;
+        Ok((Rgb16::from_rgb(r, g, b), count))
+    }
+}
+
+impl<'a> Iterator for Runs<'a> {
+    type Item = Result<(Rgb16, usize), Truncated>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let run = self.next_run();
+        if run.is_err() {
+            self.remaining = 0;
+        }
+        Some(run)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x2 image that uses every op but `OP_RGBA`
+    #[rustfmt::skip]
+    const IMAGE: [u8; 32] = [
+        b'q', b'o', b'i', b'f', 0, 0, 0, 4, 0, 0, 0, 2, 3, 0,
+        // (100, 50, 200), which is hashed to 19
+        OP_RGB, 100, 50, 200,
+        // +1, -1, 0
+        OP_DIFF | 0b11_01_10,
+        // green +10, then red and blue +10-3 and +10+4
+        OP_LUMA | 42, 0x5c,
+        // 3 more of that
+        OP_RUN | 2,
+        // back to the first pixel
+        OP_INDEX | 19,
+        OP_RUN,
+        // the end marker
+        0, 0, 0, 0, 0, 0, 0, 1,
+    ];
+
+    #[test]
+    fn every_op() {
+        let image = QoiImage::new(&IMAGE);
+        assert_eq!((image.width, image.height), (4, 2));
+
+        let first = Rgb16::from_rgb(100, 50, 200);
+        let luma = Rgb16::from_rgb(108, 59, 214);
+        let runs: Vec<_> = image.runs().collect();
+        assert_eq!(
+            runs,
+            [
+                Ok((first, 1)),
+                Ok((Rgb16::from_rgb(101, 49, 200), 1)),
+                Ok((luma, 1)),
+                Ok((luma, 3)),
+                Ok((first, 1)),
+                Ok((first, 1)),
+            ],
+        );
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        // the end marker isn't needed, we stop after the last pixel
+        assert!(QoiImage::new(&IMAGE[..24]).runs().all(|run| run.is_ok()));
+
+        // in the middle of `OP_LUMA`
+        let runs: Vec<_> = QoiImage::new(&IMAGE[..20]).runs().collect();
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[2], Err(Truncated));
+    }
+}
@@ -0,0 +1,130 @@
+//! Run-length encoded RGB565, as produced by `procure.py` (`.rle565`).
+//!
+//! The stream is a sequence of packets, each starting with a header byte `h`
+//! - `h & 0x80 != 0`: a run, the next pixel is repeated `(h & 0x7f) + 1` times
+//! - otherwise: `h + 1` literal pixels follow
+//!
+//! Pixels are stored exactly as they are in a `.rgb565` file.
+
+use super::{Error, Truncated};
+use crate::lcd::{Lcd, Rgb16};
+
+#[derive(Clone, Copy)]
+pub struct RleImage<'a> {
+    pub width: u16,
+    pub height: u16,
+    pub data: &'a [u8],
+}
+
+impl<'a> RleImage<'a> {
+    pub const fn new(width: u16, height: u16, data: &'a [u8]) -> Self {
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn runs(&self) -> Runs<'a> {
+        Runs {
+            data: self.data,
+            literals: 0,
+        }
+    }
+
+    /// Decode this image straight onto the display, see [`super::Image::draw`]
+    pub fn draw<L: Lcd>(&self, lcd: &mut L, pos: (i32, i32)) -> Result<(), Error<L::Error>> {
+        super::draw_runs(lcd, pos, (self.width, self.height), self.runs())
+    }
+}
+
+/// Iterator over the `(colour, count)` runs of an [`RleImage`]. If the data
+/// ends in the middle of a packet, this gives [`Truncated`] and then stops.
+pub struct Runs<'a> {
+    data: &'a [u8],
+    /// literal pixels left in the current packet
+    literals: usize,
+}
+
+impl<'a> Runs<'a> {
+    fn next_pixel(&mut self) -> Result<Rgb16, Truncated> {
+        if self.data.len() < 2 {
+            self.data = &[];
+            self.literals = 0;
+            return Err(Truncated);
+        }
+        let (pixel, rest) = self.data.split_at(2);
+        self.data = rest;
+        Ok(Rgb16::from_bytes(pixel)[0])
+    }
+}
+
+impl<'a> Iterator for Runs<'a> {
+    type Item = Result<(Rgb16, usize), Truncated>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.literals != 0 {
+            self.literals -= 1;
+            return Some(self.next_pixel().map(|pixel| (pixel, 1)));
+        }
+
+        let (&header, rest) = self.data.split_first()?;
+        self.data = rest;
+        let count = (header & 0x7f) as usize + 1;
+        if header & 0x80 != 0 {
+            Some(self.next_pixel().map(|pixel| (pixel, count)))
+        } else {
+            self.literals = count - 1;
+            Some(self.next_pixel().map(|pixel| (pixel, 1)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A: [u8; 2] = [0x12, 0x34];
+    const B: [u8; 2] = [0x56, 0x78];
+    const C: [u8; 2] = [0x9a, 0xbc];
+
+    fn pixel(bytes: [u8; 2]) -> Rgb16 {
+        Rgb16::from_bytes(&bytes)[0]
+    }
+
+    /// a run of 5 `A`s, then `B`, `C`, `A` as literals
+    #[rustfmt::skip]
+    const DATA: [u8; 11] = [
+        0x80 | 4, A[0], A[1],
+        2, B[0], B[1], C[0], C[1], A[0], A[1],
+        // and the start of another run
+        0x80,
+    ];
+
+    #[test]
+    fn runs_and_literals() {
+        let image = RleImage::new(4, 2, &DATA[..10]);
+        let runs: Vec<_> = image.runs().collect();
+        assert_eq!(
+            runs,
+            [
+                Ok((pixel(A), 5)),
+                Ok((pixel(B), 1)),
+                Ok((pixel(C), 1)),
+                Ok((pixel(A), 1)),
+            ],
+        );
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        // in the middle of the literals
+        let runs: Vec<_> = RleImage::new(4, 2, &DATA[..7]).runs().collect();
+        assert_eq!(runs, [Ok((pixel(A), 5)), Ok((pixel(B), 1)), Err(Truncated)],);
+
+        // a run header with no pixel after it
+        let runs: Vec<_> = RleImage::new(4, 2, &DATA).runs().collect();
+        assert_eq!(runs.len(), 5);
+        assert_eq!(runs[4], Err(Truncated));
+    }
+}
@@ -0,0 +1,135 @@
+/// One step of a controller's init sequence: send `command` with `data`,
+/// then wait `delay_ms` before the next step
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InitCommand {
+    pub command: u8,
+    pub data: &'static [u8],
+    pub delay_ms: u32,
+}
+
+impl InitCommand {
+    pub const fn new(command: u8, data: &'static [u8]) -> Self {
+        Self {
+            command,
+            data,
+            delay_ms: 0,
+        }
+    }
+
+    pub const fn with_delay(self, delay_ms: u32) -> Self {
+        Self { delay_ms, ..self }
+    }
+}
+
+/// A list of [`InitCommand`]s for a driver to run, e.g. one of the presets
+/// in [`crate::st7789::presets`], optionally with some of its entries
+/// swapped out:
+/// ```no_run
+/// const GAMMA: &[u8] = &[0xd0, 0x04, 0x0d, /* ... */];
+/// let init = presets::PIMORONI.patched(&[InitCommand::new(GMCTRP1, GAMMA)]);
+/// lcd.init_with(init, &mut delay)?;
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct InitSequence<'a> {
+    base: &'a [InitCommand],
+    /// Each one replaces the entries in `base` with the same command, and
+    /// later ones win, so patching a preset that is itself patched keeps
+    /// both
+    patches: [&'a [InitCommand]; MAX_PATCHES],
+    patches_len: usize,
+}
+
+/// How many times a sequence can be [`InitSequence::patched`]
+pub const MAX_PATCHES: usize = 4;
+
+impl<'a> InitSequence<'a> {
+    pub const fn new(base: &'a [InitCommand]) -> Self {
+        Self {
+            base,
+            patches: [&[]; MAX_PATCHES],
+            patches_len: 0,
+        }
+    }
+
+    /// This sequence with the entries for the commands in `patches`
+    /// replaced, on top of any patches it already has. To add commands you
+    /// need your own sequence, so this panics (at compile time, for a
+    /// `const`) if a patch's command isn't in the sequence, or if it has
+    /// been patched [`MAX_PATCHES`] times already.
+    pub const fn patched(self, patches: &'a [InitCommand]) -> Self {
+        let mut i = 0;
+        while i < patches.len() {
+            assert!(
+                self.contains(patches[i].command),
+                "patched a command that isn't in the init sequence"
+            );
+            i += 1;
+        }
+        assert!(self.patches_len < MAX_PATCHES, "too many patches");
+
+        let mut layers = self.patches;
+        layers[self.patches_len] = patches;
+        Self {
+            patches: layers,
+            patches_len: self.patches_len + 1,
+            ..self
+        }
+    }
+
+    const fn contains(&self, command: u8) -> bool {
+        let mut i = 0;
+        while i < self.base.len() {
+            if self.base[i].command == command {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    /// The commands to send, in order, with patches applied
+    pub fn commands(&self) -> impl Iterator<Item = &InitCommand> + '_ {
+        let patches = &self.patches[..self.patches_len];
+        self.base.iter().map(move |base| {
+            patches
+                .iter()
+                .rev()
+                .flat_map(|layer| layer.iter())
+                .find(|patch| patch.command == base.command)
+                .unwrap_or(base)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: InitSequence = InitSequence::new(&[
+        InitCommand::new(0x01, &[]),
+        InitCommand::new(0xb7, &[0x14]),
+        InitCommand::new(0xbb, &[0x37]),
+    ]);
+    const ONCE: InitSequence = BASE.patched(&[
+        InitCommand::new(0xb7, &[0x35]),
+        InitCommand::new(0xbb, &[0x19]),
+    ]);
+    const TWICE: InitSequence = ONCE.patched(&[InitCommand::new(0xbb, &[0x20])]);
+
+    fn data(sequence: InitSequence) -> Vec<&'static [u8]> {
+        sequence.commands().map(|step| step.data).collect()
+    }
+
+    #[test]
+    fn patches_stack() {
+        assert_eq!(data(ONCE), [&[][..], &[0x35], &[0x19]]);
+        assert_eq!(data(TWICE), [&[][..], &[0x35], &[0x20]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn patching_a_missing_command_panics() {
+        const COLMOD: &[InitCommand] = &[InitCommand::new(0x3a, &[0x55])];
+        BASE.patched(COLMOD);
+    }
+}
@@ -0,0 +1,174 @@
+//! How bytes get to the controller, separate from what they mean. Every
+//! controller driver is generic over a [`DisplayInterface`], so it works
+//! over any of the buses here:
+//! - [`spi::SpiInterface`]: 4-wire SPI, with a DC pin
+//! - [`spi::Spi3Wire`]: 3-wire SPI, with DC sent as a 9th bit
+//! - [`parallel::Parallel8080`]: an 8 or 16-bit 8080 parallel bus
+//! - [`dma::DmaInterface`]: 4-wire SPI, sending data in the background
+//!   through a [`dma::Transport`]
+//!
+//! In tests, `mock` has buses and pins that record what they're asked to
+//! do, for checking an interface without a display attached.
+
+use core::fmt::Debug;
+
+use crate::{
+    driver::{CASET, RASET},
+    lcd::{self, Rgb16},
+};
+
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{self, OutputPin},
+};
+
+pub mod dma;
+#[cfg(test)]
+pub mod mock;
+pub mod parallel;
+pub mod spi;
+
+/// Something went wrong talking to the panel
+#[derive(Debug)]
+pub enum Error<BusE> {
+    /// The SPI device, or the data lines of a parallel bus, failed
+    Bus(BusE),
+    /// One of the control pins (DC, WR, backlight or reset) failed
+    Pin(digital::ErrorKind),
+    /// The bus can't read from the controller, e.g. 3-wire SPI, which would
+    /// have to turn the data line round, or the controller can't do the
+    /// config's pixel format
+    Unsupported,
+}
+
+impl<BusE> Error<BusE> {
+    pub(crate) fn pin(e: impl digital::Error) -> Self {
+        Error::Pin(e.kind())
+    }
+}
+
+/// The MIPI-style way of talking to a controller: a command byte in command
+/// mode (DC low), then its parameters in data mode (DC high)
+pub trait DisplayInterface {
+    type BusError: Debug;
+
+    /// Send `bytes` in command mode
+    fn write_commands(&mut self, bytes: &[u8]) -> Result<(), Error<Self::BusError>>;
+
+    /// Send `bytes` in data mode
+    fn write_data(&mut self, bytes: &[u8]) -> Result<(), Error<Self::BusError>>;
+
+    /// Send `pixels` in data mode, after a `RAMWR`. Buses that take a whole
+    /// pixel at a time override this.
+    fn write_pixels(&mut self, pixels: &[Rgb16]) -> Result<(), Error<Self::BusError>> {
+        self.write_data(Rgb16::as_bytes(pixels))
+    }
+
+    /// Send `count` pixels that `fill` draws, in data mode, after a
+    /// `RAMWR`, see [`crate::lcd::Lcd::write_rgb_with`]. Buses with buffers
+    /// of their own have `fill` draw straight into them.
+    fn write_pixels_with(
+        &mut self,
+        count: usize,
+        fill: &mut dyn FnMut(usize, &mut [Rgb16]),
+    ) -> Result<(), Error<Self::BusError>> {
+        lcd::write_with(count, fill, |pixels| self.write_pixels(pixels))
+    }
+
+    /// Send `pixel` `count` times in data mode, after a `RAMWR`. By default
+    /// this goes through [`DisplayInterface::write_pixels`] from a small
+    /// buffer, buses that can do better override it.
+    fn write_repeated(&mut self, pixel: Rgb16, count: usize) -> Result<(), Error<Self::BusError>> {
+        lcd::repeat(pixel, count, |pixels| self.write_pixels(pixels))
+    }
+
+    /// Make sure everything written so far has actually been sent
+    fn flush(&mut self) -> Result<(), Error<Self::BusError>> {
+        Ok(())
+    }
+
+    fn write_command(&mut self, command: u8, data: &[u8]) -> Result<(), Error<Self::BusError>> {
+        self.write_commands(&[command])?;
+        self.write_data(data)
+    }
+
+    /// Send `command` and read its response into `buf`, in one transaction
+    /// because the controller gives up on a read when CS goes high. Any
+    /// dummy clocks the controller sends first end up at the start of `buf`.
+    /// Buses that can't read return [`Error::Unsupported`].
+    fn read_command(&mut self, command: u8, buf: &mut [u8]) -> Result<(), Error<Self::BusError>> {
+        let _ = (command, buf);
+        Err(Error::Unsupported)
+    }
+
+    /// Set the column and row address window (inclusive, in RAM
+    /// coordinates) with `CASET` and `RASET`, which every MIPI-style
+    /// controller here shares
+    fn set_address_window(
+        &mut self,
+        x: (u16, u16),
+        y: (u16, u16),
+    ) -> Result<(), Error<Self::BusError>> {
+        fn b(n: u16) -> (u8, u8) {
+            ((n >> 8) as u8, n as u8)
+        }
+
+        self.write_command(CASET, &[b(x.0).0, b(x.0).1, b(x.1).0, b(x.1).1])?;
+        self.write_command(RASET, &[b(y.0).0, b(y.0).1, b(y.1).0, b(y.1).1])
+    }
+}
+
+/// How long to hold the reset line low, and how long the controller needs
+/// afterwards before it takes commands, from its datasheet. Each driver has
+/// its chip's as `RESET_TIMING`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ResetTiming {
+    /// the shortest pulse on RESX (RES# on the SSDs) that's sure to reset it
+    pub pulse_us: u32,
+    /// for a reset while the panel is awake, which is the longer one. The
+    /// MIPI controllers take up to 120ms then, but only 5ms from sleep. The
+    /// SSDs are ready as soon as the line goes high again.
+    pub recovery_ms: u32,
+}
+
+/// Pulse the reset line. With [`crate::lcd::NoPin`] this is just a delay,
+/// and we rely on the `SWRESET` in the init sequence.
+pub fn hardware_reset<BusE>(
+    rst: &mut impl OutputPin,
+    timing: ResetTiming,
+    delay: &mut impl DelayNs,
+) -> Result<(), Error<BusE>> {
+    rst.set_low().map_err(Error::pin)?;
+    delay.delay_us(timing.pulse_us);
+    rst.set_high().map_err(Error::pin)?;
+    delay.delay_ms(timing.recovery_ms);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{cell::RefCell, convert::Infallible};
+
+    use super::*;
+    use crate::interface::mock::{Event, Log, MockDelay, MockPin};
+
+    #[test]
+    fn hardware_reset_pulses_and_then_waits() {
+        let log = RefCell::new(Log::<8>::new());
+        let timing = ResetTiming {
+            pulse_us: 10,
+            recovery_ms: 120,
+        };
+        hardware_reset::<Infallible>(&mut MockPin::new(&log, 1), timing, &mut MockDelay(&log))
+            .unwrap();
+        assert_eq!(
+            log.borrow().events(),
+            [
+                Event::Pin(1, false),
+                Event::Delay(10),
+                Event::Pin(1, true),
+                Event::Delay(120_000),
+            ],
+        );
+    }
+}
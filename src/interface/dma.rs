@@ -0,0 +1,334 @@
+use core::fmt::Debug;
+
+use embedded_hal::{digital::OutputPin, spi::SpiDevice};
+
+use super::{DisplayInterface, Error};
+use crate::lcd::Rgb16;
+
+/// A buffer given back by [`Transport::wait`], and how its transfer went
+pub type Sent<E> = (&'static mut [u8], Result<(), E>);
+
+/// Something that can send a buffer in the background, like SPI with DMA.
+/// The buffers are `'static` and handed over for the whole transfer, since
+/// the hardware reads them after [`Transport::start`] returns.
+pub trait Transport {
+    type Error: Debug;
+
+    /// Send `bytes` and wait for them to go, for commands
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+
+    /// Start sending the first `len` bytes of `buf`, and return straight
+    /// away. There's never more than one transfer at a time, we always
+    /// [`Transport::wait`] before starting another.
+    fn start(&mut self, buf: &'static mut [u8], len: usize);
+
+    /// Wait for the transfer from [`Transport::start`] to finish, and give
+    /// its buffer back, whether or not it worked
+    fn wait(&mut self) -> Sent<Self::Error>;
+}
+
+/// A [`Transport`] that doesn't do anything in the background, so `start`
+/// sends the whole buffer before it returns. For buses without DMA, or to
+/// fake one in tests.
+pub struct SpiTransport<Spi: SpiDevice> {
+    pub spi: Spi,
+    done: Option<Sent<Spi::Error>>,
+}
+
+impl<Spi: SpiDevice> SpiTransport<Spi> {
+    pub fn new(spi: Spi) -> Self {
+        Self { spi, done: None }
+    }
+}
+
+impl<Spi: SpiDevice> Transport for SpiTransport<Spi> {
+    type Error = Spi::Error;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.spi.write(bytes)
+    }
+
+    fn start(&mut self, buf: &'static mut [u8], len: usize) {
+        let result = self.spi.write(&buf[..len]);
+        self.done = Some((buf, result));
+    }
+
+    /// Panics if nothing was started
+    fn wait(&mut self) -> Sent<Self::Error> {
+        self.done
+            .take()
+            .expect("waited without starting a transfer")
+    }
+}
+
+/// 4-wire SPI like [`super::spi::SpiInterface`], but data is double
+/// buffered: it's copied into one buffer and sent in the background while
+/// the caller gets on with the next thing, e.g. drawing the next glyph into
+/// the other buffer. We only wait when both buffers are busy, or to send a
+/// command, since DC can't change in the middle of a transfer.
+pub struct DmaInterface<T: Transport, Dc: OutputPin> {
+    pub transport: T,
+    pub dc: Dc,
+    /// the buffer we copy into next
+    fill: Option<&'static mut [u8]>,
+    /// the other buffer, `None` while it's being sent
+    spare: Option<&'static mut [u8]>,
+}
+
+impl<T, Dc> DmaInterface<T, Dc>
+where
+    T: Transport,
+    Dc: OutputPin,
+{
+    /// `buffers` don't have to be the same size, but bigger ones mean fewer
+    /// transfers. Panics if either one is too small for a pixel.
+    pub fn new(transport: T, dc: Dc, [fill, spare]: [&'static mut [u8]; 2]) -> Self {
+        assert!(fill.len() >= 2 && spare.len() >= 2);
+        Self {
+            transport,
+            dc,
+            fill: Some(fill),
+            spare: Some(spare),
+        }
+    }
+
+    /// Wait for the transfer in flight (if there is one) to finish
+    fn wait(&mut self) -> Result<(), Error<T::Error>> {
+        if self.spare.is_some() {
+            return Ok(());
+        }
+        let (buf, result) = self.transport.wait();
+        self.spare = Some(buf);
+        result.map_err(Error::Bus)
+    }
+
+    /// Start sending the first `len` bytes of the fill buffer, and fill the
+    /// other one next. If the transfer before failed, we still start this
+    /// one before saying so, rather than losing what's just been copied.
+    fn send(&mut self, len: usize) -> Result<(), Error<T::Error>> {
+        let result = self.wait();
+        let buf = self.fill.take().unwrap();
+        self.transport.start(buf, len);
+        self.fill = self.spare.take();
+        result
+    }
+}
+
+impl<T, Dc> DisplayInterface for DmaInterface<T, Dc>
+where
+    T: Transport,
+    Dc: OutputPin,
+{
+    type BusError = T::Error;
+
+    /// Commands are short, so they're sent straight away, after whatever
+    /// data is still going
+    fn write_commands(&mut self, bytes: &[u8]) -> Result<(), Error<T::Error>> {
+        self.wait()?;
+        self.dc.set_low().map_err(Error::pin)?;
+        self.transport.write(bytes).map_err(Error::Bus)
+    }
+
+    /// Copy `bytes` into the fill buffer a buffer's worth at a time, and
+    /// start sending each one without waiting for it
+    fn write_data(&mut self, mut bytes: &[u8]) -> Result<(), Error<T::Error>> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        // commands never stay in flight, so this can't change DC under one
+        self.dc.set_high().map_err(Error::pin)?;
+        while !bytes.is_empty() {
+            let fill = self.fill.as_deref_mut().unwrap();
+            let len = fill.len().min(bytes.len());
+            fill[..len].copy_from_slice(&bytes[..len]);
+            self.send(len)?;
+            bytes = &bytes[len..];
+        }
+        Ok(())
+    }
+
+    /// Have `fill` draw straight into each buffer, and send it while it
+    /// draws into the other one
+    fn write_pixels_with(
+        &mut self,
+        count: usize,
+        fill: &mut dyn FnMut(usize, &mut [Rgb16]),
+    ) -> Result<(), Error<T::Error>> {
+        self.dc.set_high().map_err(Error::pin)?;
+        let mut start = 0;
+        while start != count {
+            let pixels = Rgb16::from_bytes_mut(self.fill.as_deref_mut().unwrap());
+            let len = pixels.len().min(count - start);
+            fill(start, &mut pixels[..len]);
+            self.send(2 * len)?;
+            start += len;
+        }
+        Ok(())
+    }
+
+    /// Fill each buffer with `pixel` and send it, as many times as it takes
+    fn write_repeated(&mut self, pixel: Rgb16, count: usize) -> Result<(), Error<T::Error>> {
+        let pixel = [pixel];
+        let pixel = Rgb16::as_bytes(&pixel);
+        let len = 2 * count;
+        let mut sent = 0;
+        self.dc.set_high().map_err(Error::pin)?;
+        while sent != len {
+            let fill = self.fill.as_deref_mut().unwrap();
+            let n = fill.len().min(len - sent);
+            // a buffer might be an odd length, so go by where we are overall
+            for (i, byte) in fill[..n].iter_mut().enumerate() {
+                *byte = pixel[(sent + i) % 2];
+            }
+            self.send(n)?;
+            sent += n;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error<T::Error>> {
+        self.wait()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{boxed::Box, vec, vec::Vec};
+
+    use super::*;
+    use crate::lcd::NoPin;
+
+    #[derive(Debug, PartialEq)]
+    enum Sent {
+        Write(Vec<u8>),
+        /// which buffer, and what was in it
+        Start(usize, Vec<u8>),
+        Wait,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Failed;
+
+    /// Writes down what it's asked to send, and fails the transfers that
+    /// `fail` says to
+    struct FakeTransport {
+        buffers: [*const u8; 2],
+        in_flight: Option<&'static mut [u8]>,
+        transfers: usize,
+        fail: fn(usize) -> bool,
+        sent: Vec<Sent>,
+    }
+
+    impl Transport for FakeTransport {
+        type Error = Failed;
+
+        fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+            assert!(self.in_flight.is_none(), "wrote with a transfer in flight");
+            self.sent.push(Sent::Write(bytes.to_vec()));
+            Ok(())
+        }
+
+        fn start(&mut self, buf: &'static mut [u8], len: usize) {
+            assert!(self.in_flight.is_none(), "started two transfers");
+            let which = self.buffers.iter().position(|&b| b == buf.as_ptr());
+            self.sent
+                .push(Sent::Start(which.unwrap(), buf[..len].to_vec()));
+            self.in_flight = Some(buf);
+        }
+
+        fn wait(&mut self) -> super::Sent<Self::Error> {
+            self.sent.push(Sent::Wait);
+            let buf = self.in_flight.take().unwrap();
+            self.transfers += 1;
+            match (self.fail)(self.transfers) {
+                true => (buf, Err(Failed)),
+                false => (buf, Ok(())),
+            }
+        }
+    }
+
+    fn interface(fail: fn(usize) -> bool) -> DmaInterface<FakeTransport, NoPin> {
+        let buffers = [0, 1].map(|_| &mut Box::leak(Box::new([0; 4]))[..]);
+        let transport = FakeTransport {
+            buffers: [buffers[0].as_ptr(), buffers[1].as_ptr()],
+            in_flight: None,
+            transfers: 0,
+            fail,
+            sent: Vec::new(),
+        };
+        DmaInterface::new(transport, NoPin, buffers)
+    }
+
+    #[test]
+    fn data_ping_pongs_between_the_buffers() {
+        let mut iface = interface(|_| false);
+        iface.write_data(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]).unwrap();
+        iface.write_commands(&[0x2c]).unwrap();
+        assert_eq!(
+            iface.transport.sent,
+            [
+                Sent::Start(0, vec![1, 2, 3, 4]),
+                // the first buffer is only waited for when we need it again
+                Sent::Wait,
+                Sent::Start(1, vec![5, 6, 7, 8]),
+                Sent::Wait,
+                Sent::Start(0, vec![9, 10]),
+                // and a command waits for the data before it
+                Sent::Wait,
+                Sent::Write(vec![0x2c]),
+            ]
+        );
+    }
+
+    #[test]
+    fn pixels_are_drawn_into_the_buffers() {
+        let mut iface = interface(|_| false);
+        let mut drawn = Vec::new();
+        iface
+            .write_pixels_with(5, &mut |start, pixels| {
+                drawn.push((start, pixels.len()));
+                pixels.fill(Rgb16::from(start as u16));
+            })
+            .unwrap();
+        iface.flush().unwrap();
+        assert_eq!(drawn, [(0, 2), (2, 2), (4, 1)]);
+        assert_eq!(
+            iface.transport.sent,
+            [
+                Sent::Start(0, vec![0, 0, 0, 0]),
+                Sent::Wait,
+                Sent::Start(1, vec![0, 2, 0, 2]),
+                Sent::Wait,
+                Sent::Start(0, vec![0, 4]),
+                Sent::Wait,
+            ]
+        );
+    }
+
+    #[test]
+    fn a_failed_transfer_is_reported_once_and_we_carry_on() {
+        let mut iface = interface(|transfer| transfer == 1);
+        // the first transfer fails, which we find out after copying the
+        // second chunk, and we stop there
+        let result = iface.write_data(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        assert!(matches!(result, Err(Error::Bus(Failed))));
+        iface.flush().unwrap();
+        // but the chunk we'd already copied still goes out
+        assert_eq!(
+            iface.transport.sent,
+            [
+                Sent::Start(0, vec![1, 2, 3, 4]),
+                Sent::Wait,
+                Sent::Start(1, vec![5, 6, 7, 8]),
+                Sent::Wait,
+            ]
+        );
+        iface.write_data(&[0; 8]).unwrap();
+        iface.flush().unwrap();
+
+        let mut iface = interface(|transfer| transfer == 1);
+        iface.write_repeated(Rgb16::BLACK, 1).unwrap();
+        assert!(matches!(iface.flush(), Err(Error::Bus(Failed))));
+        assert!(matches!(iface.write_commands(&[0]), Ok(())));
+    }
+}
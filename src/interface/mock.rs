@@ -0,0 +1,216 @@
+//! An SPI device, a parallel bus and pins that write down what they're asked
+//! to do, in one shared [`Log`], so an interface can be checked without a
+//! display:
+//! ```no_run
+//! let log = RefCell::new(Log::<16>::new());
+//! let mut iface = SpiInterface { spi: MockSpi(&log), dc: MockPin::new(&log, 0) };
+//! iface.write_command(0x36, &[0x60])?;
+//! assert_eq!(
+//!     log.borrow().events(),
+//!     &[Event::Pin(0, false), Event::Write(0x36), Event::Pin(0, true), Event::Write(0x60)],
+//! );
+//! ```
+
+use core::{cell::RefCell, convert::Infallible};
+
+use embedded_hal::{delay::DelayNs, digital, spi};
+
+use super::{parallel::ParallelBus, spi::SpiInterface};
+use crate::{
+    driver::{Controller, Driver},
+    init_sequence::InitSequence,
+    panel::PanelConfig,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    /// a byte was written to the [`MockSpi`]
+    Write(u8),
+    /// the [`MockPin`] with this id was set high (`true`) or low
+    Pin(u8, bool),
+    /// the [`MockBus`] data lines were set to this
+    Bus(u16),
+    /// a [`MockDelay`] was asked to wait this many microseconds
+    Delay(u32),
+}
+
+/// The first `N` events, anything after that is dropped
+pub struct Log<const N: usize> {
+    events: [Event; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for Log<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Log<N> {
+    pub const fn new() -> Self {
+        Self {
+            events: [Event::Write(0); N],
+            len: 0,
+        }
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events[..self.len]
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn push(&mut self, event: Event) {
+        if let Some(slot) = self.events.get_mut(self.len) {
+            *slot = event;
+            self.len += 1;
+        }
+    }
+}
+
+/// An SPI device that never fails. Reads give back zeroes.
+pub struct MockSpi<'a, const N: usize>(pub &'a RefCell<Log<N>>);
+
+impl<'a, const N: usize> spi::ErrorType for MockSpi<'a, N> {
+    type Error = Infallible;
+}
+
+impl<'a, const N: usize> spi::SpiDevice for MockSpi<'a, N> {
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let mut log = self.0.borrow_mut();
+        for op in operations {
+            match op {
+                spi::Operation::Write(bytes) => {
+                    bytes.iter().for_each(|&byte| log.push(Event::Write(byte)))
+                }
+                spi::Operation::Transfer(read, write) => {
+                    write.iter().for_each(|&byte| log.push(Event::Write(byte)));
+                    read.fill(0);
+                }
+                spi::Operation::TransferInPlace(buf) => {
+                    buf.iter().for_each(|&byte| log.push(Event::Write(byte)));
+                    buf.fill(0);
+                }
+                spi::Operation::Read(buf) => buf.fill(0),
+                spi::Operation::DelayNs(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The data lines of a parallel bus that never fail, 16 bits wide if `WIDE`
+pub struct MockBus<'a, const N: usize, const WIDE: bool>(pub &'a RefCell<Log<N>>);
+
+impl<'a, const N: usize, const WIDE: bool> ParallelBus for MockBus<'a, N, WIDE> {
+    type Error = Infallible;
+
+    const WIDE: bool = WIDE;
+
+    fn set(&mut self, word: u16) -> Result<(), Self::Error> {
+        self.0.borrow_mut().push(Event::Bus(word));
+        Ok(())
+    }
+}
+
+/// An output pin that never fails, `id` tells it apart from the others in
+/// the log
+pub struct MockPin<'a, const N: usize> {
+    log: &'a RefCell<Log<N>>,
+    id: u8,
+}
+
+impl<'a, const N: usize> MockPin<'a, N> {
+    pub fn new(log: &'a RefCell<Log<N>>, id: u8) -> Self {
+        Self { log, id }
+    }
+}
+
+impl<'a, const N: usize> digital::ErrorType for MockPin<'a, N> {
+    type Error = Infallible;
+}
+
+impl<'a, const N: usize> digital::OutputPin for MockPin<'a, N> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.log.borrow_mut().push(Event::Pin(self.id, false));
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.log.borrow_mut().push(Event::Pin(self.id, true));
+        Ok(())
+    }
+}
+
+/// A delay that doesn't wait, since nothing on the other end needs time
+pub struct NoDelay;
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+/// A delay that doesn't wait either, but writes down how long it was asked
+/// to, rounded up to microseconds
+pub struct MockDelay<'a, const N: usize>(pub &'a RefCell<Log<N>>);
+
+impl<'a, const N: usize> DelayNs for MockDelay<'a, N> {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delay_us(ns.div_ceil(1000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.0.borrow_mut().push(Event::Delay(us));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delay_us(ms * 1000);
+    }
+}
+
+/// The ids of the DC pin in [`spi_interface`], and of the RST pin in the
+/// driver tests
+pub const DC: u8 = 0;
+pub const RST: u8 = 1;
+
+pub type MockSpiInterface<'a, const N: usize> = SpiInterface<MockSpi<'a, N>, MockPin<'a, N>>;
+
+/// A 4-wire [`SpiInterface`] over a [`MockSpi`], with its DC pin as [`DC`]
+pub fn spi_interface<const N: usize>(log: &RefCell<Log<N>>) -> MockSpiInterface<'_, N> {
+    SpiInterface {
+        spi: MockSpi(log),
+        dc: MockPin::new(log, DC),
+    }
+}
+
+/// A driver for `C` over [`spi_interface`], with its RST pin as [`RST`]
+pub fn driver<'a, C: Controller, const N: usize>(
+    log: &'a RefCell<Log<N>>,
+    config: PanelConfig,
+    sequence: InitSequence<'static>,
+) -> Driver<C, MockSpiInterface<'a, N>, MockPin<'a, N>> {
+    Driver::new(spi_interface(log), MockPin::new(log, RST), config, sequence)
+}
+
+/// What [`spi_interface`] should send for `command` with `data`
+pub fn command(command: u8, data: &[u8]) -> Vec<Event> {
+    let mut events = vec![
+        Event::Pin(DC, false),
+        Event::Write(command),
+        Event::Pin(DC, true),
+    ];
+    events.extend(data.iter().map(|&byte| Event::Write(byte)));
+    events
+}
+
+/// [`command`] for every step of `sequence`, as a MIPI-style driver sends it
+pub fn sequence(sequence: &InitSequence) -> Vec<Event> {
+    sequence
+        .commands()
+        .flat_map(|step| command(step.command, step.data))
+        .collect()
+}
@@ -0,0 +1,227 @@
+use core::fmt::Debug;
+
+use embedded_hal::digital::OutputPin;
+
+use super::{DisplayInterface, Error};
+use crate::lcd::Rgb16;
+
+/// The data lines of an 8080 bus
+pub trait ParallelBus {
+    type Error: Debug;
+
+    /// A 16-bit bus takes a whole pixel per write, an 8-bit bus takes a byte
+    const WIDE: bool;
+
+    /// Put `word` on the data lines. An 8-bit bus only uses the low byte.
+    fn set(&mut self, word: u16) -> Result<(), Self::Error>;
+}
+
+/// A parallel bus made of GPIOs, D0 first. `N` should be 8 or 16.
+pub struct PinBus<P: OutputPin, const N: usize>(pub [P; N]);
+
+impl<P: OutputPin, const N: usize> ParallelBus for PinBus<P, N> {
+    type Error = P::Error;
+
+    const WIDE: bool = N > 8;
+
+    fn set(&mut self, word: u16) -> Result<(), Self::Error> {
+        for (i, pin) in self.0.iter_mut().enumerate() {
+            if word & (1 << i) != 0 {
+                pin.set_high()?;
+            } else {
+                pin.set_low()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A controller on an 8080-style parallel bus. The controller reads the data
+/// lines when `wr` goes high. CS and RD are left to the board, i.e. tied low
+/// and high.
+pub struct Parallel8080<Bus: ParallelBus, Dc: OutputPin, Wr: OutputPin> {
+    pub bus: Bus,
+    pub dc: Dc,
+    pub wr: Wr,
+}
+
+impl<Bus, Dc, Wr> Parallel8080<Bus, Dc, Wr>
+where
+    Bus: ParallelBus,
+    Dc: OutputPin,
+    Wr: OutputPin,
+{
+    /// Put `word` on the bus and strobe WR
+    fn write_word(&mut self, word: u16) -> Result<(), Error<Bus::Error>> {
+        self.wr.set_low().map_err(Error::pin)?;
+        self.bus.set(word).map_err(Error::Bus)?;
+        self.wr.set_high().map_err(Error::pin)
+    }
+
+    /// Commands and their parameters are a byte per write, whatever the
+    /// width of the bus
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<Bus::Error>> {
+        for &byte in bytes {
+            self.write_word(byte as u16)?;
+        }
+        Ok(())
+    }
+}
+
+impl<Bus, Dc, Wr> DisplayInterface for Parallel8080<Bus, Dc, Wr>
+where
+    Bus: ParallelBus,
+    Dc: OutputPin,
+    Wr: OutputPin,
+{
+    type BusError = Bus::Error;
+
+    fn write_commands(&mut self, bytes: &[u8]) -> Result<(), Error<Bus::Error>> {
+        self.dc.set_low().map_err(Error::pin)?;
+        self.write_bytes(bytes)
+    }
+
+    fn write_data(&mut self, bytes: &[u8]) -> Result<(), Error<Bus::Error>> {
+        self.dc.set_high().map_err(Error::pin)?;
+        self.write_bytes(bytes)
+    }
+
+    fn write_pixels(&mut self, pixels: &[Rgb16]) -> Result<(), Error<Bus::Error>> {
+        if !Bus::WIDE {
+            return self.write_data(Rgb16::as_bytes(pixels));
+        }
+
+        self.dc.set_high().map_err(Error::pin)?;
+        for pixel in Rgb16::as_bytes(pixels).chunks_exact(2) {
+            self.write_word(u16::from_be_bytes([pixel[0], pixel[1]]))?;
+        }
+        Ok(())
+    }
+
+    /// No buffer needed: on a 16-bit bus (or if both bytes are the same) the
+    /// data lines stay put and we just strobe WR
+    fn write_repeated(&mut self, pixel: Rgb16, count: usize) -> Result<(), Error<Bus::Error>> {
+        self.dc.set_high().map_err(Error::pin)?;
+        let [hi, lo] = Rgb16::as_bytes(&[pixel]).try_into().unwrap();
+        if !Bus::WIDE && hi != lo {
+            for _ in 0..count {
+                self.write_word(hi as u16)?;
+                self.write_word(lo as u16)?;
+            }
+            return Ok(());
+        }
+
+        let (word, strobes) = if Bus::WIDE {
+            (u16::from_be_bytes([hi, lo]), count)
+        } else {
+            (hi as u16, 2 * count)
+        };
+        self.bus.set(word).map_err(Error::Bus)?;
+        for _ in 0..strobes {
+            self.wr.set_low().map_err(Error::pin)?;
+            self.wr.set_high().map_err(Error::pin)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::interface::mock::{Event, Log, MockBus, MockPin};
+
+    const DC: u8 = 0;
+    const WR: u8 = 1;
+
+    type Mock<'a, const WIDE: bool> =
+        Parallel8080<MockBus<'a, 64, WIDE>, MockPin<'a, 64>, MockPin<'a, 64>>;
+
+    fn iface<const WIDE: bool>(log: &RefCell<Log<64>>) -> Mock<'_, WIDE> {
+        Parallel8080 {
+            bus: MockBus(log),
+            dc: MockPin::new(log, DC),
+            wr: MockPin::new(log, WR),
+        }
+    }
+
+    /// WR goes low, the data lines change, then WR goes high to latch them
+    fn strobe(word: u16) -> [Event; 3] {
+        [
+            Event::Pin(WR, false),
+            Event::Bus(word),
+            Event::Pin(WR, true),
+        ]
+    }
+
+    const WR_ONLY: [Event; 2] = [Event::Pin(WR, false), Event::Pin(WR, true)];
+
+    #[test]
+    fn a_command_is_a_byte_per_strobe() {
+        let log = RefCell::new(Log::new());
+        iface::<true>(&log)
+            .write_command(0x2a, &[0x00, 0xef])
+            .unwrap();
+
+        let expected = [
+            &[Event::Pin(DC, false)][..],
+            &strobe(0x2a),
+            &[Event::Pin(DC, true)],
+            &strobe(0x00),
+            &strobe(0xef),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn a_wide_bus_takes_a_pixel_per_strobe() {
+        let log = RefCell::new(Log::new());
+        let pixel = Rgb16::from(0x1234);
+        iface::<true>(&log).write_pixels(&[pixel; 2]).unwrap();
+
+        let expected = [
+            &[Event::Pin(DC, true)][..],
+            &strobe(0x1234),
+            &strobe(0x1234),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn repeats_only_strobe_wr() {
+        let log = RefCell::new(Log::new());
+        iface::<true>(&log)
+            .write_repeated(Rgb16::from(0x1234), 2)
+            .unwrap();
+        let expected = [
+            &[Event::Pin(DC, true), Event::Bus(0x1234)][..],
+            &WR_ONLY,
+            &WR_ONLY,
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+
+        // on a narrow bus that only works if both bytes are the same
+        log.borrow_mut().clear();
+        iface::<false>(&log)
+            .write_repeated(Rgb16::from(0x4242), 1)
+            .unwrap();
+        let expected = [
+            &[Event::Pin(DC, true), Event::Bus(0x42)][..],
+            &WR_ONLY,
+            &WR_ONLY,
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+
+        log.borrow_mut().clear();
+        iface::<false>(&log)
+            .write_repeated(Rgb16::from(0x1234), 1)
+            .unwrap();
+        let expected = [&[Event::Pin(DC, true)][..], &strobe(0x12), &strobe(0x34)].concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+}
@@ -0,0 +1,206 @@
+use embedded_hal::{
+    digital::OutputPin,
+    spi::{Operation, SpiDevice},
+};
+
+use super::{DisplayInterface, Error};
+
+/// A controller on a 4-wire SPI bus, with a DC pin to tell commands from data
+pub struct SpiInterface<Spi: SpiDevice, Dc: OutputPin> {
+    pub spi: Spi,
+    pub dc: Dc,
+}
+
+impl<Spi, Dc> SpiInterface<Spi, Dc>
+where
+    Spi: SpiDevice,
+    Dc: OutputPin,
+{
+    /// SPI write_bytes
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error<Spi::Error>> {
+        self.spi.write(bytes).map_err(Error::Bus)
+    }
+}
+
+impl<Spi, Dc> DisplayInterface for SpiInterface<Spi, Dc>
+where
+    Spi: SpiDevice,
+    Dc: OutputPin,
+{
+    type BusError = Spi::Error;
+
+    /// `write_bytes`, but first set command mode
+    fn write_commands(&mut self, bytes: &[u8]) -> Result<(), Error<Spi::Error>> {
+        self.dc.set_low().map_err(Error::pin)?;
+        self.write_bytes(bytes)
+    }
+
+    /// `write_bytes`, but first set data mode
+    fn write_data(&mut self, bytes: &[u8]) -> Result<(), Error<Spi::Error>> {
+        self.dc.set_high().map_err(Error::pin)?;
+        self.write_bytes(bytes)
+    }
+
+    fn flush(&mut self) -> Result<(), Error<Spi::Error>> {
+        self.spi.write(&[]).map_err(Error::Bus)
+    }
+
+    /// This needs MISO, which a lot of modules don't break out. DC stays low
+    /// while we read, the controller doesn't look at it.
+    fn read_command(&mut self, command: u8, buf: &mut [u8]) -> Result<(), Error<Spi::Error>> {
+        self.dc.set_low().map_err(Error::pin)?;
+        self.spi
+            .transaction(&mut [Operation::Write(&[command]), Operation::Read(buf)])
+            .map_err(Error::Bus)
+    }
+}
+
+/// A controller on a 3-wire SPI bus, where every byte is sent as a 9-bit
+/// word with DC in front of it.
+///
+/// SPI only moves whole bytes, so we send words in groups of 8 (9 bytes).
+/// Words that don't fill a group wait for the next write, and
+/// [`DisplayInterface::flush`] fills the group up with `NOP`s. A `NOP` ends
+/// a `RAMWR`, so we only flush after a whole command.
+pub struct Spi3Wire<Spi: SpiDevice> {
+    pub spi: Spi,
+    /// words that don't fill a group yet
+    pending: [u16; 8],
+    pending_len: usize,
+}
+
+impl<Spi: SpiDevice> Spi3Wire<Spi> {
+    pub fn new(spi: Spi) -> Self {
+        Self {
+            spi,
+            pending: [0; 8],
+            pending_len: 0,
+        }
+    }
+
+    /// Queue up `bytes` with the DC bit set to `dc`, and send every group
+    /// that we fill
+    fn write_words(&mut self, dc: bool, bytes: &[u8]) -> Result<(), Error<Spi::Error>> {
+        // a few groups at a time, so we don't do a transaction per group
+        let mut buf = [0; 9 * 8];
+        let mut len = 0;
+        for &byte in bytes {
+            self.pending[self.pending_len] = (dc as u16) << 8 | byte as u16;
+            self.pending_len += 1;
+            if self.pending_len == self.pending.len() {
+                pack(&self.pending, &mut buf[len..len + 9]);
+                self.pending_len = 0;
+                len += 9;
+                if len == buf.len() {
+                    self.spi.write(&buf).map_err(Error::Bus)?;
+                    len = 0;
+                }
+            }
+        }
+        if len != 0 {
+            self.spi.write(&buf[..len]).map_err(Error::Bus)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pack 8 9-bit words into 9 bytes, MSB first
+fn pack(words: &[u16; 8], out: &mut [u8]) {
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut out = out.iter_mut();
+    for &word in words {
+        acc = acc << 9 | word as u32;
+        bits += 9;
+        while bits >= 8 {
+            bits -= 8;
+            *out.next().unwrap() = (acc >> bits) as u8;
+        }
+        acc &= (1 << bits) - 1;
+    }
+}
+
+impl<Spi: SpiDevice> DisplayInterface for Spi3Wire<Spi> {
+    type BusError = Spi::Error;
+
+    fn write_commands(&mut self, bytes: &[u8]) -> Result<(), Error<Spi::Error>> {
+        self.write_words(false, bytes)
+    }
+
+    fn write_data(&mut self, bytes: &[u8]) -> Result<(), Error<Spi::Error>> {
+        self.write_words(true, bytes)
+    }
+
+    /// Fill the last group up with `NOP`s (all zeroes) and send it
+    fn flush(&mut self) -> Result<(), Error<Spi::Error>> {
+        if self.pending_len == 0 {
+            return Ok(());
+        }
+        self.pending[self.pending_len..].fill(0);
+        self.pending_len = 0;
+        let mut buf = [0; 9];
+        pack(&self.pending, &mut buf);
+        self.spi.write(&buf).map_err(Error::Bus)
+    }
+
+    /// A whole command, so we can flush it straight away. Otherwise it
+    /// might not be sent until the next command, e.g. after a delay.
+    fn write_command(&mut self, command: u8, data: &[u8]) -> Result<(), Error<Spi::Error>> {
+        self.write_words(false, &[command])?;
+        self.write_words(true, data)?;
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::{
+        interface::mock::{Event, Log, MockSpi},
+        lcd::Rgb16,
+    };
+
+    fn writes(bytes: &[u8]) -> Vec<Event> {
+        bytes.iter().map(|&byte| Event::Write(byte)).collect()
+    }
+
+    #[test]
+    fn pack_9_bit_words() {
+        let mut out = [0; 9];
+        pack(
+            &[0x136, 0x060, 0x000, 0x1ff, 0x0a5, 0x15a, 0x001, 0x100],
+            &mut out,
+        );
+        assert_eq!(out, [0x9b, 0x18, 0x00, 0x1f, 0xf5, 0x2d, 0x68, 0x03, 0x00]);
+    }
+
+    #[test]
+    fn a_whole_command_is_sent_straight_away() {
+        let log = RefCell::new(Log::<16>::new());
+        let mut iface = Spi3Wire::new(MockSpi(&log));
+        iface.write_command(0x36, &[0x60]).unwrap();
+
+        let mut expected = [0; 9];
+        pack(&[0x036, 0x160, 0, 0, 0, 0, 0, 0], &mut expected);
+        assert_eq!(log.borrow().events(), writes(&expected));
+    }
+
+    #[test]
+    fn flush_sends_the_end_of_a_pixel_stream() {
+        let log = RefCell::new(Log::<16>::new());
+        let mut iface = Spi3Wire::new(MockSpi(&log));
+        let pixel = [Rgb16::from_rgb(255, 0, 0)];
+        let [hi, lo] = [Rgb16::as_bytes(&pixel)[0], Rgb16::as_bytes(&pixel)[1]];
+        iface.write_commands(&[0x2c]).unwrap();
+        iface.write_pixels(&pixel).unwrap();
+        assert_eq!(log.borrow().events(), []);
+
+        iface.flush().unwrap();
+        let mut expected = [0; 9];
+        let words = [0x02c, 0x100 | hi as u16, 0x100 | lo as u16, 0, 0, 0, 0, 0];
+        pack(&words, &mut expected);
+        assert_eq!(log.borrow().events(), writes(&expected));
+    }
+}
@@ -1,6 +1,9 @@
-use core::fmt::Debug;
+use core::{convert::Infallible, fmt::Debug};
 
-use crate::types::OutputPinV2;
+use embedded_hal::{
+    delay::DelayNs,
+    digital::{ErrorType, OutputPin},
+};
 
 #[repr(C)]
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -34,6 +37,46 @@ impl Rgb16 {
         Self::from(inverted ^ 0xffff)
     }
 
+    /// Like [`Rgb16::from_rgb`], but with ordered (Bayer) dithering so that
+    /// gradients don't band. `(x, y)` is where the pixel will end up on the
+    /// display, which picks the threshold.
+    pub fn from_rgb_dithered(x: u16, y: u16, r: u8, g: u8, b: u8) -> Self {
+        // thresholds in 0..16
+        const BAYER: [[u8; 4]; 4] = [
+            [0, 8, 2, 10], //
+            [12, 4, 14, 6],
+            [3, 11, 1, 9],
+            [15, 7, 13, 5],
+        ];
+
+        // red and blue lose 3 bits (a step of 8), green loses 2 (a step of 4)
+        let t = BAYER[y as usize % 4][x as usize % 4];
+        Self::from_rgb(
+            r.saturating_add(t / 2),
+            g.saturating_add(t / 4),
+            b.saturating_add(t / 2),
+        )
+    }
+
+    /// The 5-6-5 channels of this colour, undoing the inversion
+    fn channels(self) -> [u16; 3] {
+        let rgb = u16::from_be_bytes([self.0, self.1]) ^ 0xffff;
+        [rgb >> 11, (rgb >> 5) & 0b11_1111, rgb & 0b1_1111]
+    }
+
+    /// Blend between `self` (`t = 0`) and `other` (`t = 255`). This is done
+    /// per channel in RGB565 space, so there's no gamma correction.
+    pub fn lerp(self, other: Self, t: u8) -> Self {
+        let t = t as u32;
+        let mix = |a: u16, b: u16| ((a as u32 * (255 - t) + b as u32 * t + 127) / 255) as u16;
+
+        let [r1, g1, b1] = self.channels();
+        let [r2, g2, b2] = other.channels();
+        let inverted = (mix(r1, r2) << 11) | (mix(g1, g2) << 5) | mix(b1, b2);
+
+        Self::from(inverted ^ 0xffff)
+    }
+
     pub fn as_bytes(buf: &[Rgb16]) -> &[u8] {
         // SAFETY: safe because Rgb16 is size 2 (repr C) and we resize to
         // twice the length. This is just a mirror of `buf`, so it can live
@@ -44,14 +87,532 @@ impl Rgb16 {
     pub const fn from_bytes(buf: &[u8]) -> &[Rgb16] {
         unsafe { core::slice::from_raw_parts(buf as *const [u8] as _, buf.len() / 2) }
     }
+
+    /// The whole pixels in `buf`, to draw straight into, e.g. a DMA buffer.
+    /// An odd byte at the end is left out.
+    pub fn from_bytes_mut(buf: &mut [u8]) -> &mut [Rgb16] {
+        // SAFETY: as for `as_bytes`, Rgb16 is 2 bytes with an alignment of 1,
+        // and we only take the whole pixels
+        unsafe { core::slice::from_raw_parts_mut(buf as *mut [u8] as _, buf.len() / 2) }
+    }
+}
+
+/// A rectangle of pixels on the display, `(x, y)` is the top-left corner
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub const fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The overlap of two rectangles, which might be empty (zero width or
+    /// height)
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let x_end = self.x_end().min(other.x_end());
+        let y_end = self.y_end().min(other.y_end());
+        Rect::new(
+            x,
+            y,
+            x_end.saturating_sub(x as u32) as u16,
+            y_end.saturating_sub(y as u32) as u16,
+        )
+    }
+
+    /// One past the right edge, which might not fit in a `u16`
+    pub const fn x_end(&self) -> u32 {
+        self.x as u32 + self.width as u32
+    }
+
+    /// One past the bottom edge, which might not fit in a `u16`
+    pub const fn y_end(&self) -> u32 {
+        self.y as u32 + self.height as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The inclusive `(x, y)` ranges for [`Lcd::prepare_window`], which
+    /// only make sense if the rectangle isn't empty
+    pub fn window(&self) -> ((u16, u16), (u16, u16)) {
+        (
+            (self.x, (self.x_end() - 1) as u16),
+            (self.y, (self.y_end() - 1) as u16),
+        )
+    }
+
+    pub fn area(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+}
+
+/// The part of the display that can actually be seen
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum SafeArea {
+    /// all of it
+    #[default]
+    Rectangle,
+    /// the biggest circle that fits, centred, e.g. on a round panel
+    Circle,
+}
+
+impl SafeArea {
+    /// Whether every pixel of `rect` can be seen on a display of
+    /// `(width, height)`
+    pub fn contains(self, (width, height): (u16, u16), rect: &Rect) -> bool {
+        if rect.x_end() > width as u32 || rect.y_end() > height as u32 {
+            return false;
+        }
+        match self {
+            SafeArea::Rectangle => true,
+            SafeArea::Circle => {
+                // everything is doubled so that the centres of the pixels
+                // and of the display are whole numbers
+                fn furthest(start: u16, len: u16, size: u16) -> i64 {
+                    let first = 2 * start as i64 + 1;
+                    let last = 2 * (start as i64 + len as i64) - 1;
+                    let centre = size as i64;
+                    (first - centre).abs().max((last - centre).abs())
+                }
+
+                let dx = furthest(rect.x, rect.width, width);
+                let dy = furthest(rect.y, rect.height, height);
+                let radius = width.min(height) as i64;
+                dx * dx + dy * dy <= radius * radius
+            }
+        }
+    }
 }
 
 pub trait Lcd {
+    /// Whatever can go wrong talking to the display, e.g. a bus error
+    type Error: Debug;
+
     /// Set the window, such that subsequent writes will write to this
     /// region -- this means we also want to set `RAMWR`
-    fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16));
+    fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error>;
 
     /// Write a single pixel to the LCD. `rgb` should not be mutated such
     /// that it will have a visible effect on `rgb`. What we want
-    fn write_rgb(&mut self, rgb: &[Rgb16]);
+    fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error>;
+
+    /// Write `count` pixels that `fill` draws a piece at a time: it's given
+    /// the index of the first pixel and a buffer to draw that many into.
+    /// Drivers that can hand out their own buffer (like a
+    /// [`crate::interface::dma::DmaInterface`]) do, so nothing is drawn
+    /// twice and nothing big goes on the stack.
+    fn write_rgb_with(
+        &mut self,
+        count: usize,
+        fill: &mut dyn FnMut(usize, &mut [Rgb16]),
+    ) -> Result<(), Self::Error> {
+        write_with(count, fill, |rgb| self.write_rgb(rgb))
+    }
+
+    /// Make sure everything written so far has reached the display. Some
+    /// buses (and pixel formats) hold on to the end of a
+    /// [`Lcd::write_rgb`] until there's more to send with it, so call this
+    /// when you're done drawing for now. [`crate::term::Term`], the images
+    /// and the text renderer do it for you.
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The `(width, height)` of the display in pixels, used for clipping
+    fn dimensions(&self) -> (u16, u16);
+
+    /// Which part of [`Lcd::dimensions`] can be seen. Drawing outside of it
+    /// is fine, it just won't show up.
+    fn safe_area(&self) -> SafeArea {
+        SafeArea::Rectangle
+    }
+
+    /// Fill `w` by `h` pixels, with the top-left at `(x, y)`, with `colour`.
+    /// Whatever is off the display is left out, and if that's everything,
+    /// nothing is sent.
+    fn fill_rect(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        colour: Rgb16,
+    ) -> Result<(), Self::Error> {
+        let Some(rect) = clip(self, Rect::new(x, y, w, h)) else {
+            return Ok(());
+        };
+        let (x, y) = rect.window();
+        self.prepare_window(x, y)?;
+        write_repeated(self, colour, rect.area())?;
+        self.flush()
+    }
+
+    /// Fill the whole display with `colour`
+    fn clear(&mut self, colour: Rgb16) -> Result<(), Self::Error> {
+        let (width, height) = self.dimensions();
+        self.fill_rect(0, 0, width, height, colour)
+    }
+}
+
+/// Write `colour` `count` times, from a small buffer that we send over and
+/// over. Drivers use this for [`Lcd::fill_rect`] when they can't do better.
+pub fn write_repeated<L: Lcd + ?Sized>(
+    lcd: &mut L,
+    colour: Rgb16,
+    count: usize,
+) -> Result<(), L::Error> {
+    repeat(colour, count, |rgb| lcd.write_rgb(rgb))
+}
+
+/// [`write_repeated`] for anything that takes pixels, e.g.
+/// [`crate::interface::DisplayInterface::write_repeated`]
+pub(crate) fn repeat<E>(
+    colour: Rgb16,
+    count: usize,
+    mut write: impl FnMut(&[Rgb16]) -> Result<(), E>,
+) -> Result<(), E> {
+    let buf = [colour; 32];
+    let mut left = count;
+    while left != 0 {
+        let len = left.min(buf.len());
+        write(&buf[..len])?;
+        left -= len;
+    }
+    Ok(())
+}
+
+/// The part of `rect` that's on `lcd`, for [`Lcd::fill_rect`], or `None`
+/// if none of it is
+pub(crate) fn clip<L: Lcd + ?Sized>(lcd: &L, rect: Rect) -> Option<Rect> {
+    let (width, height) = lcd.dimensions();
+    let rect = rect.intersection(&Rect::new(0, 0, width, height));
+    (!rect.is_empty()).then_some(rect)
+}
+
+/// Draw `count` pixels with `fill` into a small buffer, and `write` each
+/// bufferful, for the [`Lcd::write_rgb_with`] of anything without a buffer
+/// of its own
+pub(crate) fn write_with<E>(
+    count: usize,
+    fill: &mut dyn FnMut(usize, &mut [Rgb16]),
+    mut write: impl FnMut(&[Rgb16]) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut buf = [Rgb16::BLACK; 32];
+    let mut start = 0;
+    while start != count {
+        let len = (count - start).min(buf.len());
+        fill(start, &mut buf[..len]);
+        write(&buf[..len])?;
+        start += len;
+    }
+    Ok(())
+}
+
+/// [`Lcd`] for async code, e.g. an embassy task. This has to be its own trait
+/// (rather than `Lcd` with async methods) so that the blocking drivers don't
+/// need an executor.
+///
+/// Output isn't non-blocking yet: the only implementation is [`Blocking`],
+/// since we don't depend on `embedded-hal-async` and so have no driver over
+/// an async `SpiDevice`. What you get is the yields between windows in e.g.
+/// [`crate::term::Term::display_async`], so one window's worth of SPI is the
+/// longest the other tasks wait.
+// the futures aren't `Send`, which is fine on a single-core executor
+#[allow(async_fn_in_trait)]
+pub trait AsyncLcd {
+    type Error: Debug;
+
+    /// [`Lcd::prepare_window`]
+    async fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error>;
+
+    /// [`Lcd::write_rgb`]
+    async fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error>;
+
+    /// [`Lcd::write_rgb_with`]
+    async fn write_rgb_with(
+        &mut self,
+        count: usize,
+        fill: &mut dyn FnMut(usize, &mut [Rgb16]),
+    ) -> Result<(), Self::Error> {
+        let mut buf = [Rgb16::BLACK; 32];
+        let mut start = 0;
+        while start != count {
+            let len = (count - start).min(buf.len());
+            fill(start, &mut buf[..len]);
+            self.write_rgb(&buf[..len]).await?;
+            start += len;
+        }
+        Ok(())
+    }
+
+    /// [`Lcd::flush`]
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn dimensions(&self) -> (u16, u16);
+
+    fn safe_area(&self) -> SafeArea {
+        SafeArea::Rectangle
+    }
+}
+
+/// A blocking [`Lcd`] as an [`AsyncLcd`]. Each write still blocks until it's
+/// on the wire, but other tasks get to run between windows.
+pub struct Blocking<L: Lcd>(pub L);
+
+impl<L: Lcd> AsyncLcd for Blocking<L> {
+    type Error = L::Error;
+
+    async fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error> {
+        self.0.prepare_window(x, y)
+    }
+
+    async fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error> {
+        self.0.write_rgb(rgb)
+    }
+
+    async fn write_rgb_with(
+        &mut self,
+        count: usize,
+        fill: &mut dyn FnMut(usize, &mut [Rgb16]),
+    ) -> Result<(), Self::Error> {
+        self.0.write_rgb_with(count, fill)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush()
+    }
+
+    fn dimensions(&self) -> (u16, u16) {
+        self.0.dimensions()
+    }
+
+    fn safe_area(&self) -> SafeArea {
+        self.0.safe_area()
+    }
+}
+
+/// Power saving, for the controllers that have it. RAM is kept in every
+/// mode, so nothing needs redrawing afterwards.
+pub trait PowerManagement: Lcd {
+    /// Put the controller to sleep: the panel goes off, and so do the
+    /// oscillator and the booster. It can't be woken up for 120ms.
+    fn sleep(&mut self, delay: &mut impl DelayNs) -> Result<(), Self::Error>;
+
+    /// Wake up from [`PowerManagement::sleep`]. It can't be put back to sleep
+    /// for 120ms.
+    fn wake(&mut self, delay: &mut impl DelayNs) -> Result<(), Self::Error>;
+
+    /// Blank the panel, or show it again, without touching RAM, e.g. to
+    /// draw a whole screen and then show it all at once
+    fn set_display_on(&mut self, on: bool) -> Result<(), Self::Error>;
+
+    /// Idle mode only shows 8 colours (the top bit of each channel), which
+    /// takes less power
+    fn set_idle(&mut self, idle: bool) -> Result<(), Self::Error>;
+
+    /// Only drive the panel rows in `rows` (inclusive), the rest are
+    /// blanked. `None` goes back to normal mode. These are rows of the panel
+    /// in its native orientation, so they're columns if it's rotated by 90
+    /// degrees.
+    fn set_partial(&mut self, rows: Option<(u16, u16)>) -> Result<(), Self::Error>;
+}
+
+/// An output pin that isn't connected to anything, for drivers with optional
+/// pins (e.g. reset)
+pub struct NoPin;
+
+impl ErrorType for NoPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for NoPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// An [`Lcd`] (and [`AsyncLcd`]) that writes down the windows it was asked
+/// for, so drawing code can be checked without a display
+#[cfg(test)]
+pub mod mock {
+    use core::convert::Infallible;
+
+    use super::{AsyncLcd, Lcd, Rgb16};
+
+    /// `(x, y)` ranges, inclusive, like [`Lcd::prepare_window`]
+    pub type Window = ((u16, u16), (u16, u16));
+
+    /// Remembers the first `N` windows, anything after that is dropped
+    pub struct MockLcd<const N: usize> {
+        pub dimensions: (u16, u16),
+        windows: [Window; N],
+        windows_len: usize,
+        /// how many pixels have been written since the last window
+        pub pixels: usize,
+        pub flushes: usize,
+    }
+
+    impl<const N: usize> MockLcd<N> {
+        pub const fn new(dimensions: (u16, u16)) -> Self {
+            Self {
+                dimensions,
+                windows: [((0, 0), (0, 0)); N],
+                windows_len: 0,
+                pixels: 0,
+                flushes: 0,
+            }
+        }
+
+        pub fn windows(&self) -> &[Window] {
+            &self.windows[..self.windows_len]
+        }
+
+        pub fn clear(&mut self) {
+            self.windows_len = 0;
+            self.pixels = 0;
+            self.flushes = 0;
+        }
+    }
+
+    impl<const N: usize> Lcd for MockLcd<N> {
+        type Error = Infallible;
+
+        fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error> {
+            if let Some(slot) = self.windows.get_mut(self.windows_len) {
+                *slot = (x, y);
+                self.windows_len += 1;
+            }
+            self.pixels = 0;
+            Ok(())
+        }
+
+        fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error> {
+            self.pixels += rgb.len();
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.flushes += 1;
+            Ok(())
+        }
+
+        fn dimensions(&self) -> (u16, u16) {
+            self.dimensions
+        }
+    }
+
+    impl<const N: usize> AsyncLcd for MockLcd<N> {
+        type Error = Infallible;
+
+        async fn prepare_window(
+            &mut self,
+            x: (u16, u16),
+            y: (u16, u16),
+        ) -> Result<(), Self::Error> {
+            Lcd::prepare_window(self, x, y)
+        }
+
+        async fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error> {
+            Lcd::write_rgb(self, rgb)
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Lcd::flush(self)
+        }
+
+        fn dimensions(&self) -> (u16, u16) {
+            self.dimensions
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mock::MockLcd, Lcd, Rgb16};
+
+    #[test]
+    fn dithering_adds_the_bayer_threshold() {
+        // how far below the next step a channel can be and still round up
+        // there, i.e. the threshold scaled to that channel's step
+        let bumps = |channel: usize, step: u8| {
+            let mut bumps = [[0; 4]; 4];
+            for (y, row) in bumps.iter_mut().enumerate() {
+                for (x, bump) in row.iter_mut().enumerate() {
+                    *bump = (0..step)
+                        .filter(|&v| {
+                            let c = Rgb16::from_rgb_dithered(x as u16 + 4, y as u16 + 8, v, v, v);
+                            c.channels()[channel] == 1
+                        })
+                        .count();
+                }
+            }
+            bumps
+        };
+        let red = [[0, 4, 1, 5], [6, 2, 7, 3], [1, 5, 0, 4], [7, 3, 6, 2]];
+        let green = [[0, 2, 0, 2], [3, 1, 3, 1], [0, 2, 0, 2], [3, 1, 3, 1]];
+        assert_eq!(bumps(0, 8), red);
+        assert_eq!(bumps(1, 4), green);
+        assert_eq!(bumps(2, 8), red);
+
+        // black never lifts, and white doesn't wrap around
+        for (x, y) in (0..4).flat_map(|y| (0..4).map(move |x| (x, y))) {
+            assert_eq!(Rgb16::from_rgb_dithered(x, y, 0, 0, 0), Rgb16::BLACK);
+            assert_eq!(Rgb16::from_rgb_dithered(x, y, 255, 255, 255), Rgb16::WHITE);
+        }
+    }
+
+    #[test]
+    fn lerp_ends_and_midpoint() {
+        // from (not inverted) 5-6-5 channels
+        let rgb = |r: u16, g: u16, b: u16| Rgb16::from((r << 11 | g << 5 | b) ^ 0xffff);
+        let from = rgb(4, 10, 31);
+        let to = rgb(20, 60, 1);
+        assert_eq!(from.lerp(to, 0), from);
+        assert_eq!(from.lerp(to, 255), to);
+        assert_eq!(from.lerp(to, 128), rgb(12, 35, 16));
+        // the same blend from the other end
+        assert_eq!(to.lerp(from, 127), rgb(12, 35, 16));
+
+        assert_eq!(Rgb16::BLACK.lerp(Rgb16::WHITE, 128), rgb(16, 32, 16));
+    }
+
+    #[test]
+    fn fill_rect_is_clipped_to_the_display() {
+        let mut lcd = MockLcd::<4>::new((40, 32));
+        lcd.fill_rect(35, 30, 10, 10, Rgb16::BLACK).unwrap();
+        assert_eq!(lcd.windows(), &[((35, 39), (30, 31))]);
+        assert_eq!(lcd.pixels, 5 * 2);
+
+        // none of these are on the display, and they'd overflow a `u16`
+        lcd.clear();
+        lcd.fill_rect(u16::MAX, 0, 2, 2, Rgb16::BLACK).unwrap();
+        lcd.fill_rect(0, 40, u16::MAX, u16::MAX, Rgb16::BLACK)
+            .unwrap();
+        lcd.fill_rect(0, 0, 0, 10, Rgb16::BLACK).unwrap();
+        assert_eq!(lcd.windows(), &[]);
+
+        lcd.fill_rect(0, 0, u16::MAX, u16::MAX, Rgb16::BLACK)
+            .unwrap();
+        assert_eq!(lcd.windows(), &[((0, 39), (0, 31))]);
+        assert_eq!(lcd.pixels, 40 * 32);
+    }
 }
@@ -0,0 +1,24 @@
+//! The drivers and the terminal, which don't depend on the chip, so they
+//! build (and test) on the host too. The firmware itself is in `main.rs`.
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod backlight;
+pub mod driver;
+pub mod executor;
+pub mod font;
+pub mod gc9a01;
+pub mod ili9341;
+pub mod image;
+pub mod init_sequence;
+pub mod interface;
+pub mod lcd;
+pub mod panel;
+pub mod pixel_format;
+pub mod ssd1331;
+pub mod ssd1351;
+pub mod st7735;
+pub mod st7789;
+pub mod term;
+pub mod text;
+pub mod vsync;
@@ -2,18 +2,24 @@
 #![no_std]
 #![no_main]
 
+mod compat;
+#[allow(dead_code)]
 mod lazy_spinlock;
-mod lcd;
-mod st7789;
-mod term;
+#[cfg(not(feature = "self-test"))]
+mod spi_dma;
 
 mod types {
     use esp32s3_hal::prelude::*;
 
     /// I'm not quite sure what's going on here -- will leave it as this for now...
     pub trait OutputPinV2 = _embedded_hal_digital_v2_OutputPin;
+    pub trait InputPinV2 = _embedded_hal_digital_v2_InputPin;
 }
 
+#[cfg(not(feature = "self-test"))]
+use core::ptr::addr_of_mut;
+
+use embedded_hal::delay::DelayNs;
 use esp32s3_hal::{
     clock::ClockControl,
     peripherals::Peripherals,
@@ -21,14 +27,41 @@ use esp32s3_hal::{
     spi::{master::Spi, SpiMode},
     Delay, IO,
 };
+#[cfg(not(feature = "self-test"))]
+use esp32s3_hal::{dma::DmaPriority, gdma::Gdma, spi::master::dma::WithDmaSpi2};
 use esp_backtrace as _;
 use esp_println::println;
-use lcd::Rgb16;
+#[cfg(not(feature = "self-test"))]
+use lcdterm::interface::dma::DmaInterface;
+#[cfg(feature = "self-test")]
+use lcdterm::interface::spi::SpiInterface;
+use lcdterm::{
+    backlight::{Backlight, PinBacklight},
+    image::{include_rgb565, Image},
+    lcd::{Lcd, NoPin, Rgb16},
+    panel::{PanelConfig, Rotation},
+    st7789,
+    term::{self, Char, ScrollableRow, Term},
+};
 
-use crate::{
-    lcd::Lcd,
-    term::{Char, ScrollableRow, Term},
+#[cfg(feature = "self-test")]
+use crate::compat::SpiV2;
+use crate::compat::{DelayV2, PinV2};
+#[cfg(not(feature = "self-test"))]
+use crate::spi_dma::SpiDmaTransport;
+
+const PANEL: PanelConfig = PanelConfig {
+    rotation: Rotation::Deg90,
+    ..PanelConfig::P240X240
 };
+const TERM_SIZE: (usize, usize) = term::fit(PANEL.dimensions(), 1);
+
+/// Each of the two buffers pixels are drawn into while the other one is
+/// sent. Anything over a glyph (256 bytes) is fine.
+#[cfg(not(feature = "self-test"))]
+const DMA_BUFFER_LEN: usize = 1024;
+
+const LOGO: Image = Image::new(50, 50, include_rgb565!("./image/test.rgb565"));
 
 /// esp_println, but maybe I want to make it write errors?
 macro_rules! eprintln {
@@ -43,30 +76,62 @@ fn main2() -> ! {
     let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
 
     let clocks = ClockControl::max(system.clock_control).freeze();
-    let mut delay = Delay::new(&clocks);
+    let mut delay = DelayV2(Delay::new(&clocks));
 
     let cs = io.pins.gpio4;
     let sck = io.pins.gpio5;
     let mosi = io.pins.gpio6;
     let dc = io.pins.gpio7.into_push_pull_output();
     let bl = io.pins.gpio15.into_push_pull_output();
-    let mut lcd = st7789::St7789 {
-        spi: Spi::new(peripherals.SPI2, 80u32.MHz(), SpiMode::Mode0, &clocks)
-            .with_sck(sck)
-            .with_mosi(mosi)
-            .with_cs(cs),
-        dc,
-        bl,
+    let spi = Spi::new(peripherals.SPI2, 80u32.MHz(), SpiMode::Mode0, &clocks)
+        .with_sck(sck)
+        .with_mosi(mosi);
+
+    // reading the display back (with the display's SDO on MISO) needs the
+    // blocking bus, which holds CS across a command and its reply
+    #[cfg(feature = "self-test")]
+    let iface = SpiInterface {
+        spi: SpiV2::new(
+            spi.with_miso(io.pins.gpio8),
+            PinV2(cs.into_push_pull_output()),
+        ),
+        dc: PinV2(dc),
+    };
+    // otherwise pixels go by DMA, while we draw the next ones
+    #[cfg(not(feature = "self-test"))]
+    let (mut tx_descriptors, mut rx_descriptors) = ([0u32; 8 * 3], [0u32; 8 * 3]);
+    #[cfg(not(feature = "self-test"))]
+    let iface = {
+        static mut BUFFERS: [[u8; DMA_BUFFER_LEN]; 2] = [[0; DMA_BUFFER_LEN]; 2];
+        // SAFETY: this is the only place that touches `BUFFERS`, and
+        // `main2` only runs once
+        let [fill, spare] = unsafe { &mut *addr_of_mut!(BUFFERS) };
+        let channel = Gdma::new(peripherals.DMA).channel0.configure(
+            false,
+            &mut tx_descriptors,
+            &mut rx_descriptors,
+            DmaPriority::Priority0,
+        );
+        let spi = spi.with_cs(cs).with_dma(channel);
+        DmaInterface::new(SpiDmaTransport::new(spi), PinV2(dc), [fill, spare])
     };
-    lcd.init(&mut delay);
-    lcd.set_bl_high();
-
-    let mut term = Term::<30, 15>::new();
 
-    lcd.prepare_window((0, 240), (0, 240));
-    for _ in 0..=240 {
-        lcd.write_rgb(&[Rgb16::WHITE; 240]);
+    let mut lcd = st7789::St7789::new(iface, NoPin, PANEL, st7789::presets::PIMORONI);
+    lcd.init(&mut delay)
+        .expect("failed to initialise the display");
+    #[cfg(feature = "self-test")]
+    if let Err(e) = lcd.self_test(&mut esp_println::Printer) {
+        eprintln!("failed to run the display self-test: {e:?}");
     }
+    let mut backlight = PinBacklight::new(PinV2(bl));
+    backlight
+        .set_brightness(u8::MAX)
+        .expect("failed to turn on the backlight");
+
+    let mut term = Term::<{ TERM_SIZE.0 }, { TERM_SIZE.1 }>::new();
+
+    lcd.clear(Rgb16::WHITE)
+        .expect("failed to clear the display");
 
     let msgs: &[&'static [u8]] = &[
         &b"if Term::<30, 15>::works(t) {"[..],
@@ -88,7 +153,12 @@ fn main2() -> ! {
         }
     }
 
-    term.display(&mut lcd);
+    if let Err(e) = term.display(&mut lcd) {
+        eprintln!("failed to display the terminal: {e:?}");
+    }
+    if let Err(e) = term.display_image(&mut lcd, (0, 7), &LOGO) {
+        eprintln!("failed to display the logo: {e:?}");
+    }
 
     println!("here");
 
@@ -96,7 +166,14 @@ fn main2() -> ! {
 
     loop {
         region.shift(-2);
-        region.display(b"This is a scrolling message... How spooOOky! | ", &mut lcd);
+        let text = b"This is a scrolling message... How spooOOky! | ";
+        if let Err(e) = region.display(text, &mut lcd) {
+            eprintln!("failed to display the scrolling region: {e:?}");
+        }
+        // anything that didn't make it last time is still dirty
+        if let Err(e) = term.display(&mut lcd) {
+            eprintln!("failed to display the terminal: {e:?}");
+        }
         delay.delay_ms(20u32);
     }
 }
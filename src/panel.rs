@@ -0,0 +1,184 @@
+use crate::pixel_format::PixelFormat;
+
+/// Clockwise rotation of the image on the panel
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorOrder {
+    Rgb,
+    Bgr,
+}
+
+/// Everything that differs between panels on the same controller. Sizes and
+/// offsets are for the panel in its native (portrait) orientation, and are
+/// adjusted for `rotation` when we set the window. e.g. a 240x240 module in
+/// landscape:
+/// ```no_run
+/// PanelConfig { rotation: Rotation::Deg90, ..PanelConfig::P240X240 }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PanelConfig {
+    /// size of the controller's RAM, smaller panels only show part of it
+    pub ram_width: u16,
+    pub ram_height: u16,
+    pub width: u16,
+    pub height: u16,
+    /// where the panel's top-left pixel is in RAM
+    pub x_offset: u16,
+    pub y_offset: u16,
+    pub rotation: Rotation,
+    pub color_order: ColorOrder,
+    /// The panel is wired up mirrored left-to-right relative to RAM, like
+    /// every ILI9341 module we've seen
+    pub mirrored: bool,
+    /// Send `INVON`. [`crate::lcd::Rgb16`] already inverts colours for the
+    /// IPS panels that need this, so only turn it on if colours come out
+    /// inverted.
+    pub inverted: bool,
+    /// What we send over the wire. The driver converts from
+    /// [`crate::lcd::Rgb16`], so nothing else has to care.
+    pub pixel_format: PixelFormat,
+}
+
+impl PanelConfig {
+    /// ST7789 modules
+    pub const P240X240: Self = Self::new(240, 240, 0, 0);
+    pub const P135X240: Self = Self::new(135, 240, 52, 40);
+    pub const P240X280: Self = Self::new(240, 280, 0, 20);
+    pub const P240X320: Self = Self::new(240, 320, 0, 0);
+
+    /// ILI9341 modules are TN, so they don't invert colours themselves like
+    /// IPS panels do. [`crate::lcd::Rgb16`] expects that, so we invert here.
+    pub const ILI9341: Self = Self {
+        color_order: ColorOrder::Bgr,
+        mirrored: true,
+        inverted: true,
+        ..Self::P240X320
+    };
+
+    /// ST7735 modules are also TN, except the 0.96" 80x160 ones. The tab
+    /// colour is the colour of the tab on the screen protector.
+    pub const ST7735_RED_TAB: Self = Self {
+        ram_width: 128,
+        ram_height: 160,
+        color_order: ColorOrder::Bgr,
+        inverted: true,
+        ..Self::new(128, 160, 0, 0)
+    };
+    pub const ST7735_BLACK_TAB: Self = Self {
+        color_order: ColorOrder::Rgb,
+        ..Self::ST7735_RED_TAB
+    };
+    pub const ST7735_GREEN_TAB: Self = Self {
+        ram_width: 132,
+        ram_height: 162,
+        color_order: ColorOrder::Bgr,
+        inverted: true,
+        ..Self::new(128, 160, 2, 1)
+    };
+    pub const ST7735_80X160: Self = Self {
+        ram_width: 132,
+        ram_height: 162,
+        color_order: ColorOrder::Bgr,
+        ..Self::new(80, 160, 26, 1)
+    };
+
+    /// Round 240x240 GC9A01 modules, which are wired up like the ILI9341
+    pub const GC9A01: Self = Self {
+        ram_width: 240,
+        ram_height: 240,
+        color_order: ColorOrder::Bgr,
+        mirrored: true,
+        ..Self::new(240, 240, 0, 0)
+    };
+
+    /// OLEDs don't invert colours either, so like the TN panels we invert
+    /// here
+    pub const SSD1351: Self = Self {
+        ram_width: 128,
+        ram_height: 128,
+        inverted: true,
+        ..Self::new(128, 128, 0, 0)
+    };
+    pub const SSD1331: Self = Self {
+        ram_width: 96,
+        ram_height: 64,
+        inverted: true,
+        ..Self::new(96, 64, 0, 0)
+    };
+
+    /// A panel on a controller with 240x320 RAM, like the ST7789 and ILI9341
+    pub const fn new(width: u16, height: u16, x_offset: u16, y_offset: u16) -> Self {
+        Self {
+            ram_width: 240,
+            ram_height: 320,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            rotation: Rotation::Deg0,
+            color_order: ColorOrder::Rgb,
+            mirrored: false,
+            inverted: false,
+            pixel_format: PixelFormat::Rgb565,
+        }
+    }
+
+    /// The `MADCTL` value for this rotation and colour order
+    pub fn madctl(&self) -> u8 {
+        const MY: u8 = 0x80;
+        const MX: u8 = 0x40;
+        const MV: u8 = 0x20;
+        const BGR: u8 = 0x08;
+
+        let rotation = match self.rotation {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => MX | MV,
+            Rotation::Deg180 => MX | MY,
+            Rotation::Deg270 => MY | MV,
+        };
+        let rotation = if self.mirrored {
+            rotation ^ MX
+        } else {
+            rotation
+        };
+        match self.color_order {
+            ColorOrder::Rgb => rotation,
+            ColorOrder::Bgr => rotation | BGR,
+        }
+    }
+
+    /// `(width, height)` as we draw to it, i.e. after rotating
+    pub const fn dimensions(&self) -> (u16, u16) {
+        match self.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => (self.width, self.height),
+            Rotation::Deg90 | Rotation::Deg270 => (self.height, self.width),
+        }
+    }
+
+    /// Where `(0, 0)` is in the rotated RAM. Mirroring an axis moves the
+    /// panel to the other end of the RAM, so we have to count from there.
+    pub fn offset(&self) -> (u16, u16) {
+        let x_start = self.x_offset;
+        let x_end = self.ram_width - self.width - self.x_offset;
+        let (x_start, x_end) = if self.mirrored {
+            (x_end, x_start)
+        } else {
+            (x_start, x_end)
+        };
+        let y_start = self.y_offset;
+        let y_end = self.ram_height - self.height - self.y_offset;
+        match self.rotation {
+            Rotation::Deg0 => (x_start, y_start),
+            Rotation::Deg90 => (y_start, x_end),
+            Rotation::Deg180 => (x_end, y_end),
+            Rotation::Deg270 => (y_end, x_start),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Everything is drawn as [`Rgb16`], but some panels want 18-bit colour,
+//! and 12-bit colour is a quarter fewer bytes to send. Drivers convert on the
+//! way out, so the rest of the crate doesn't need to know.
+//!
+//! The inversion in [`Rgb16`] survives the conversion (each channel just
+//! gains or loses low bits), so [`crate::panel::PanelConfig::inverted`]
+//! works the same in every format.
+
+use crate::lcd::Rgb16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum PixelFormat {
+    /// 2 bytes per pixel, exactly as [`Rgb16`] is stored
+    #[default]
+    Rgb565,
+    /// 3 bytes per pixel, each channel in the top 6 bits of its byte
+    Rgb666,
+    /// 2 pixels in 3 bytes
+    Rgb444,
+}
+
+impl PixelFormat {
+    /// The `COLMOD` value for this format, which is the same on every MIPI
+    /// controller we have. Not every one has every format, e.g. the ILI9341
+    /// can't do RGB444, see [`crate::driver::Controller::PIXEL_FORMATS`].
+    pub fn colmod(self) -> u8 {
+        match self {
+            PixelFormat::Rgb565 => 0x55,
+            PixelFormat::Rgb666 => 0x66,
+            PixelFormat::Rgb444 => 0x53,
+        }
+    }
+}
+
+/// The raw (still inverted) 5-6-5 channels of `pixel`
+fn channels(pixel: Rgb16) -> (u8, u8, u8) {
+    let [hi, lo] = Rgb16::as_bytes(&[pixel]).try_into().unwrap();
+    let rgb = u16::from_be_bytes([hi, lo]);
+    ((rgb >> 11) as u8, (rgb >> 5) as u8 & 0x3f, rgb as u8 & 0x1f)
+}
+
+fn rgb666(pixel: Rgb16) -> [u8; 3] {
+    // repeat the top bit of red and blue, so that full brightness stays full
+    let (r, g, b) = channels(pixel);
+    [(r << 3 | r >> 2) & 0xfc, g << 2, (b << 3 | b >> 2) & 0xfc]
+}
+
+/// The other way round, for reading back from the panel, dropping the bits
+/// that [`Rgb16`] doesn't have
+pub fn from_rgb666(bytes: &[u8]) -> Rgb16 {
+    let r = bytes[0] as u16 >> 3;
+    let g = bytes[1] as u16 >> 2;
+    let b = bytes[2] as u16 >> 3;
+    Rgb16::from(r << 11 | g << 5 | b)
+}
+
+fn rgb444(pixel: Rgb16) -> (u8, u8, u8) {
+    let (r, g, b) = channels(pixel);
+    (r >> 1, g >> 2, b >> 1)
+}
+
+/// Converts [`Rgb16`] pixels to the bytes for a [`PixelFormat`]. RGB444 packs
+/// two pixels into three bytes, so if we're given an odd number the last one
+/// waits for the next [`PixelEncoder::write`], or for
+/// [`PixelEncoder::finish`] at the end of the window.
+#[derive(Default)]
+pub struct PixelEncoder {
+    pending: Option<Rgb16>,
+}
+
+impl PixelEncoder {
+    pub const fn new() -> Self {
+        Self { pending: None }
+    }
+
+    /// Convert `pixels` to `format` and pass them to `write` a chunk at a
+    /// time
+    pub fn write<E>(
+        &mut self,
+        format: PixelFormat,
+        pixels: &[Rgb16],
+        mut write: impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut buf = [0; 3 * 64];
+        match format {
+            PixelFormat::Rgb565 => write(Rgb16::as_bytes(pixels)),
+            PixelFormat::Rgb666 => {
+                for chunk in pixels.chunks(64) {
+                    for (out, &pixel) in buf.chunks_exact_mut(3).zip(chunk) {
+                        out.copy_from_slice(&rgb666(pixel));
+                    }
+                    write(&buf[..chunk.len() * 3])?;
+                }
+                Ok(())
+            }
+            PixelFormat::Rgb444 => {
+                let mut pixels = self
+                    .pending
+                    .take()
+                    .into_iter()
+                    .chain(pixels.iter().copied());
+                let mut len = 0;
+                while let Some(first) = pixels.next() {
+                    let Some(second) = pixels.next() else {
+                        self.pending = Some(first);
+                        break;
+                    };
+                    let (r1, g1, b1) = rgb444(first);
+                    let (r2, g2, b2) = rgb444(second);
+                    buf[len..len + 3].copy_from_slice(&[r1 << 4 | g1, b1 << 4 | r2, g2 << 4 | b2]);
+                    len += 3;
+                    if len == buf.len() {
+                        write(&buf)?;
+                        len = 0;
+                    }
+                }
+                if len != 0 {
+                    write(&buf[..len])?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Write out a pixel left over by RGB444, padded to a whole byte. The
+    /// controller ignores the padding when the next command comes.
+    pub fn finish<E>(&mut self, mut write: impl FnMut(&[u8]) -> Result<(), E>) -> Result<(), E> {
+        match self.pending.take() {
+            Some(pixel) => {
+                let (r, g, b) = rgb444(pixel);
+                write(&[r << 4 | g, b << 4])
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// raw 5-6-5 channels, so the expected bytes are easy to work out
+    const PIXELS: [(u16, u16, u16); 3] = [(22, 45, 11), (31, 0, 16), (3, 12, 7)];
+
+    fn pixels() -> Vec<Rgb16> {
+        PIXELS
+            .iter()
+            .map(|&(r, g, b)| Rgb16::from(r << 11 | g << 5 | b))
+            .collect()
+    }
+
+    /// Everything the encoder writes for each of `writes`, then `finish`
+    fn encode(format: PixelFormat, writes: &[&[Rgb16]]) -> Vec<u8> {
+        let mut encoder = PixelEncoder::new();
+        let mut bytes = vec![];
+        let mut out = |chunk: &[u8]| {
+            bytes.extend_from_slice(chunk);
+            Ok::<_, ()>(())
+        };
+        for pixels in writes {
+            encoder.write(format, pixels, &mut out).unwrap();
+        }
+        encoder.finish(&mut out).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn rgb666() {
+        let pixels = pixels();
+        let expected = [0xb4, 0xb4, 0x58, 0xfc, 0x00, 0x84, 0x18, 0x30, 0x38];
+        for n in 1..=3 {
+            let bytes = encode(PixelFormat::Rgb666, &[&pixels[..n]]);
+            assert_eq!(bytes, expected[..3 * n]);
+        }
+    }
+
+    #[test]
+    fn rgb444() {
+        let pixels = pixels();
+        // an odd pixel is padded out by `finish`
+        assert_eq!(encode(PixelFormat::Rgb444, &[&pixels[..1]]), [0xbb, 0x50]);
+        assert_eq!(
+            encode(PixelFormat::Rgb444, &[&pixels[..2]]),
+            [0xbb, 0x5f, 0x08]
+        );
+        assert_eq!(
+            encode(PixelFormat::Rgb444, &[&pixels[..3]]),
+            [0xbb, 0x5f, 0x08, 0x13, 0x30]
+        );
+
+        // or waits to be paired with the first pixel of the next write
+        assert_eq!(
+            encode(PixelFormat::Rgb444, &[&pixels[..1], &pixels[1..]]),
+            [0xbb, 0x5f, 0x08, 0x13, 0x30]
+        );
+    }
+}
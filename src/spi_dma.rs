@@ -0,0 +1,100 @@
+//! A [`Transport`] over `esp32s3_hal`'s SPI DMA, so that a
+//! [`lcdterm::interface::dma::DmaInterface`] sends each buffer in the
+//! background while the next one is drawn.
+
+use core::mem;
+
+use esp32s3_hal::{
+    dma::{DmaError, DmaTransfer},
+    gdma::Channel0,
+    peripherals::SPI2,
+    prelude::*,
+    spi::{
+        master::dma::{SpiDma, SpiDmaTransfer},
+        FullDuplexMode,
+    },
+};
+use lcdterm::interface::dma::{Sent, Transport};
+
+type Bus<'d> = SpiDma<'d, SPI2, Channel0, FullDuplexMode>;
+type Transfer<'d> = SpiDmaTransfer<'d, SPI2, Channel0, &'static mut [u8], FullDuplexMode>;
+
+/// The most one DMA transfer can send
+pub const MAX_TRANSFER: usize = 32736;
+
+// only ever printed
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum SpiDmaError {
+    Spi(esp32s3_hal::spi::Error),
+    Dma(DmaError),
+}
+
+enum State<'d> {
+    Idle(Bus<'d>),
+    /// the transfer, and the end of its buffer that isn't being sent
+    Sending(Transfer<'d>, &'static mut [u8]),
+    /// only while we're swapping one of the others out
+    Moving,
+}
+
+/// The bus is handed to each transfer and given back at the end, so this
+/// keeps whichever one has it
+pub struct SpiDmaTransport<'d> {
+    state: State<'d>,
+}
+
+impl<'d> SpiDmaTransport<'d> {
+    pub fn new(bus: Bus<'d>) -> Self {
+        Self {
+            state: State::Idle(bus),
+        }
+    }
+
+    fn bus(&mut self) -> &mut Bus<'d> {
+        match &mut self.state {
+            State::Idle(bus) => bus,
+            _ => panic!("used the bus with a transfer in flight"),
+        }
+    }
+}
+
+impl Transport for SpiDmaTransport<'_> {
+    type Error = SpiDmaError;
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.bus().write(bytes).map_err(SpiDmaError::Spi)
+    }
+
+    /// Panics if `len` is more than [`MAX_TRANSFER`]
+    fn start(&mut self, buf: &'static mut [u8], len: usize) {
+        assert!(
+            len <= MAX_TRANSFER,
+            "a DMA transfer can only be {MAX_TRANSFER} bytes"
+        );
+        let State::Idle(bus) = mem::replace(&mut self.state, State::Moving) else {
+            panic!("started two transfers");
+        };
+        let (head, tail) = buf.split_at_mut(len);
+        // the only thing `dma_write` checks is the length
+        let transfer = bus.dma_write(head).expect("checked the length");
+        self.state = State::Sending(transfer, tail);
+    }
+
+    /// Panics if nothing was started
+    fn wait(&mut self) -> Sent<Self::Error> {
+        let State::Sending(transfer, tail) = mem::replace(&mut self.state, State::Moving) else {
+            panic!("waited without starting a transfer");
+        };
+        let (head, bus, result) = match transfer.wait() {
+            Ok((head, bus)) => (head, bus, Ok(())),
+            Err((e, head, bus)) => (head, bus, Err(SpiDmaError::Dma(e))),
+        };
+        self.state = State::Idle(bus);
+        let len = head.len() + tail.len();
+        // SAFETY: `head` and `tail` were split from one buffer in `start`,
+        // so they're next to each other, and nothing else has them now
+        let buf = unsafe { core::slice::from_raw_parts_mut(head.as_mut_ptr(), len) };
+        (buf, result)
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{
+    driver::{Controller, Driver},
+    init_sequence::{InitCommand, InitSequence},
+    interface::{DisplayInterface, Error, ResetTiming},
+    panel::{ColorOrder, PanelConfig, Rotation},
+    pixel_format::PixelFormat,
+};
+
+/// A 96x64 SSD1331 OLED, with [`PanelConfig::SSD1331`], see [`Driver`].
+///
+/// Only `width`, `height`, `rotation`, `color_order` and `inverted` are used
+/// from the config, the panel has to fill the RAM. `pixel_format` has to be
+/// [`PixelFormat::Rgb565`], the 65k colours we set with `SETREMAP`.
+///
+/// There's no software reset, so without `rst`, [`Driver::reinit`] can only
+/// send the init sequence again, which puts back the registers it sets but
+/// nothing else. Connect RST if `reinit` has to recover from anything.
+pub type Ssd1331<DI, Rst> = Driver<Chip, DI, Rst>;
+
+/// What's different about the SSD1331, which doesn't speak MIPI DCS
+pub enum Chip {}
+
+impl Controller for Chip {
+    const RESET_TIMING: ResetTiming = RESET_TIMING;
+    const PIXEL_FORMATS: &'static [PixelFormat] = &[PixelFormat::Rgb565];
+
+    /// Unlike the other controllers, the SSD1331 wants a command's
+    /// parameters in command mode too
+    fn write_command<DI: DisplayInterface>(
+        iface: &mut DI,
+        command: u8,
+        params: &[u8],
+    ) -> Result<(), Error<DI::BusError>> {
+        iface.write_commands(&[command])?;
+        iface.write_commands(params)
+    }
+
+    fn configure<DI: DisplayInterface>(
+        iface: &mut DI,
+        config: &PanelConfig,
+    ) -> Result<(), Error<DI::BusError>> {
+        Self::write_command(iface, SETREMAP, &[remap(config)])?;
+        let inversion = if config.inverted {
+            INVERTDISPLAY
+        } else {
+            NORMALDISPLAY
+        };
+        Self::write_command(iface, inversion, &[])?;
+        Self::write_command(iface, DISPLAYON, &[])
+    }
+
+    /// When we're rotated sideways the controller still wants columns and
+    /// rows in RAM order
+    fn set_window<DI: DisplayInterface>(
+        iface: &mut DI,
+        config: &PanelConfig,
+        x: (u16, u16),
+        y: (u16, u16),
+    ) -> Result<(), Error<DI::BusError>> {
+        let (x, y) = match config.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => (x, y),
+            Rotation::Deg90 | Rotation::Deg270 => (y, x),
+        };
+        Self::write_command(iface, SETCOLUMN, &[x.0 as u8, x.1 as u8])?;
+        Self::write_command(iface, SETROW, &[y.0 as u8, y.1 as u8])
+    }
+
+    /// There's no `RAMWR`, anything we write in data mode goes to the window
+    fn start_write<DI: DisplayInterface>(_iface: &mut DI) -> Result<(), Error<DI::BusError>> {
+        Ok(())
+    }
+}
+
+/// The `SETREMAP` value for this rotation and colour order
+fn remap(config: &PanelConfig) -> u8 {
+    const COLOR_65K: u8 = 0x40;
+    const COM_SPLIT: u8 = 0x20;
+    const COM_REMAP: u8 = 0x10;
+    const BGR: u8 = 0x04;
+    const COLUMN_REMAP: u8 = 0x02;
+    const VERTICAL: u8 = 0x01;
+
+    let rotation = match config.rotation {
+        Rotation::Deg0 => COM_REMAP | COLUMN_REMAP,
+        Rotation::Deg90 => COM_REMAP | VERTICAL,
+        Rotation::Deg180 => 0,
+        Rotation::Deg270 => COLUMN_REMAP | VERTICAL,
+    };
+    let rotation = COLOR_65K | COM_SPLIT | rotation;
+    match config.color_order {
+        ColorOrder::Rgb => rotation,
+        ColorOrder::Bgr => rotation | BGR,
+    }
+}
+
+pub const RESET_TIMING: ResetTiming = ResetTiming {
+    pulse_us: 3,
+    recovery_ms: 1,
+};
+
+pub const SETCOLUMN: u8 = 0x15;
+pub const SETROW: u8 = 0x75;
+
+pub const CONTRASTA: u8 = 0x81;
+pub const CONTRASTB: u8 = 0x82;
+pub const CONTRASTC: u8 = 0x83;
+pub const MASTERCURRENT: u8 = 0x87;
+pub const PRECHARGEA: u8 = 0x8A;
+pub const PRECHARGEB: u8 = 0x8B;
+pub const PRECHARGEC: u8 = 0x8C;
+
+pub const SETREMAP: u8 = 0xA0;
+pub const STARTLINE: u8 = 0xA1;
+pub const DISPLAYOFFSET: u8 = 0xA2;
+pub const NORMALDISPLAY: u8 = 0xA4;
+pub const DISPLAYALLON: u8 = 0xA5;
+pub const DISPLAYALLOFF: u8 = 0xA6;
+pub const INVERTDISPLAY: u8 = 0xA7;
+pub const SETMULTIPLEX: u8 = 0xA8;
+pub const SETMASTER: u8 = 0xAD;
+pub const DISPLAYOFF: u8 = 0xAE;
+pub const DISPLAYON: u8 = 0xAF;
+
+pub const POWERMODE: u8 = 0xB0;
+pub const PRECHARGE: u8 = 0xB1;
+pub const CLOCKDIV: u8 = 0xB3;
+pub const PRECHARGELEVEL: u8 = 0xBB;
+pub const VCOMH: u8 = 0xBE;
+
+/// Init sequences for the SSD1331 modules we've seen
+pub mod presets {
+    use super::*;
+
+    /// From Adafruit's SSD1331 library, for their 0.96" 96x64 module
+    pub const ADAFRUIT: InitSequence = InitSequence::new(&[
+        InitCommand::new(DISPLAYOFF, &[]),
+        InitCommand::new(STARTLINE, &[0x00]),
+        InitCommand::new(DISPLAYOFFSET, &[0x00]),
+        InitCommand::new(NORMALDISPLAY, &[]),
+        InitCommand::new(SETMULTIPLEX, &[0x3f]),
+        // external supply
+        InitCommand::new(SETMASTER, &[0x8e]),
+        InitCommand::new(POWERMODE, &[0x0b]),
+        InitCommand::new(PRECHARGE, &[0x31]),
+        InitCommand::new(CLOCKDIV, &[0xf0]),
+        InitCommand::new(PRECHARGEA, &[0x64]),
+        InitCommand::new(PRECHARGEB, &[0x78]),
+        InitCommand::new(PRECHARGEC, &[0x64]),
+        InitCommand::new(PRECHARGELEVEL, &[0x3a]),
+        InitCommand::new(VCOMH, &[0x3e]),
+        InitCommand::new(MASTERCURRENT, &[0x06]),
+        InitCommand::new(CONTRASTA, &[0x91]),
+        InitCommand::new(CONTRASTB, &[0x50]),
+        InitCommand::new(CONTRASTC, &[0x7d]),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::{
+        interface::mock::NoDelay,
+        interface::{
+            mock::{Event, Log, MockPin, MockSpi},
+            spi::SpiInterface,
+        },
+        lcd::{Lcd, NoPin},
+    };
+
+    #[test]
+    fn window_parameters_are_commands() {
+        let log = RefCell::new(Log::<16>::new());
+        let iface = SpiInterface {
+            spi: MockSpi(&log),
+            dc: MockPin::new(&log, 0),
+        };
+        let mut lcd = Ssd1331::new(iface, NoPin, PanelConfig::SSD1331, presets::ADAFRUIT);
+        lcd.prepare_window((0, 95), (0, 63)).unwrap();
+
+        let low = Event::Pin(0, false);
+        assert_eq!(
+            log.borrow().events(),
+            [
+                [
+                    low,
+                    Event::Write(SETCOLUMN),
+                    low,
+                    Event::Write(0),
+                    Event::Write(95)
+                ],
+                [
+                    low,
+                    Event::Write(SETROW),
+                    low,
+                    Event::Write(0),
+                    Event::Write(63)
+                ],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn only_rgb565_is_supported() {
+        let log = RefCell::new(Log::<16>::new());
+        let iface = SpiInterface {
+            spi: MockSpi(&log),
+            dc: MockPin::new(&log, 0),
+        };
+        let config = PanelConfig {
+            pixel_format: PixelFormat::Rgb666,
+            ..PanelConfig::SSD1331
+        };
+        let mut lcd = Ssd1331::new(iface, NoPin, config, presets::ADAFRUIT);
+        assert!(matches!(lcd.init(&mut NoDelay), Err(Error::Unsupported)));
+        assert_eq!(log.borrow().events(), []);
+    }
+}
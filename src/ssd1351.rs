@@ -0,0 +1,230 @@
+use crate::{
+    driver::{Controller, Driver},
+    init_sequence::{InitCommand, InitSequence},
+    interface::{DisplayInterface, Error, ResetTiming},
+    panel::{ColorOrder, PanelConfig, Rotation},
+    pixel_format::PixelFormat,
+};
+
+/// A 128x128 SSD1351 OLED, with [`PanelConfig::SSD1351`], see [`Driver`].
+///
+/// Only `width`, `height`, `rotation`, `color_order` and `inverted` are used
+/// from the config, the panel has to fill the RAM. `pixel_format` has to be
+/// [`PixelFormat::Rgb565`], the 65k colours we set with `SETREMAP`.
+///
+/// There's no software reset, so without `rst`, [`Driver::reinit`] can only
+/// send the init sequence again, which puts back the registers it sets but
+/// nothing else. Connect RST if `reinit` has to recover from anything.
+pub type Ssd1351<DI, Rst> = Driver<Chip, DI, Rst>;
+
+/// What's different about the SSD1351, which doesn't speak MIPI DCS
+pub enum Chip {}
+
+impl Controller for Chip {
+    const RESET_TIMING: ResetTiming = RESET_TIMING;
+    const PIXEL_FORMATS: &'static [PixelFormat] = &[PixelFormat::Rgb565];
+
+    fn configure<DI: DisplayInterface>(
+        iface: &mut DI,
+        config: &PanelConfig,
+    ) -> Result<(), Error<DI::BusError>> {
+        iface.write_command(SETREMAP, &[remap(config)])?;
+        iface.write_command(STARTLINE, &[0])?;
+        let inversion = if config.inverted {
+            INVERTDISPLAY
+        } else {
+            NORMALDISPLAY
+        };
+        iface.write_command(inversion, &[])?;
+        iface.write_command(DISPLAYON, &[])
+    }
+
+    /// When we're rotated sideways the controller still wants columns and
+    /// rows in RAM order
+    fn set_window<DI: DisplayInterface>(
+        iface: &mut DI,
+        config: &PanelConfig,
+        x: (u16, u16),
+        y: (u16, u16),
+    ) -> Result<(), Error<DI::BusError>> {
+        let (x, y) = match config.rotation {
+            Rotation::Deg0 | Rotation::Deg180 => (x, y),
+            Rotation::Deg90 | Rotation::Deg270 => (y, x),
+        };
+        iface.write_command(SETCOLUMN, &[x.0 as u8, x.1 as u8])?;
+        iface.write_command(SETROW, &[y.0 as u8, y.1 as u8])
+    }
+
+    fn start_write<DI: DisplayInterface>(iface: &mut DI) -> Result<(), Error<DI::BusError>> {
+        iface.write_commands(&[WRITERAM])
+    }
+}
+
+/// The `SETREMAP` value for this rotation and colour order. Unlike
+/// `MADCTL`, the 65k colour depth lives in here too.
+fn remap(config: &PanelConfig) -> u8 {
+    const COLOR_65K: u8 = 0x40;
+    const COM_SPLIT: u8 = 0x20;
+    const COM_REMAP: u8 = 0x10;
+    const RGB: u8 = 0x04;
+    const COLUMN_REMAP: u8 = 0x02;
+    const VERTICAL: u8 = 0x01;
+
+    let rotation = match config.rotation {
+        Rotation::Deg0 => COM_REMAP,
+        Rotation::Deg90 => COM_REMAP | COLUMN_REMAP | VERTICAL,
+        Rotation::Deg180 => COLUMN_REMAP,
+        Rotation::Deg270 => VERTICAL,
+    };
+    let rotation = COLOR_65K | COM_SPLIT | rotation;
+    match config.color_order {
+        ColorOrder::Rgb => rotation | RGB,
+        ColorOrder::Bgr => rotation,
+    }
+}
+
+pub const RESET_TIMING: ResetTiming = ResetTiming {
+    pulse_us: 2,
+    recovery_ms: 1,
+};
+
+pub const SETCOLUMN: u8 = 0x15;
+pub const SETROW: u8 = 0x75;
+pub const WRITERAM: u8 = 0x5C;
+pub const READRAM: u8 = 0x5D;
+
+pub const SETREMAP: u8 = 0xA0;
+pub const STARTLINE: u8 = 0xA1;
+pub const DISPLAYOFFSET: u8 = 0xA2;
+pub const DISPLAYALLOFF: u8 = 0xA4;
+pub const DISPLAYALLON: u8 = 0xA5;
+pub const NORMALDISPLAY: u8 = 0xA6;
+pub const INVERTDISPLAY: u8 = 0xA7;
+
+pub const FUNCTIONSELECT: u8 = 0xAB;
+pub const DISPLAYOFF: u8 = 0xAE;
+pub const DISPLAYON: u8 = 0xAF;
+
+pub const PRECHARGE: u8 = 0xB1;
+pub const DISPLAYENHANCE: u8 = 0xB2;
+pub const CLOCKDIV: u8 = 0xB3;
+pub const SETVSL: u8 = 0xB4;
+pub const SETGPIO: u8 = 0xB5;
+pub const PRECHARGE2: u8 = 0xB6;
+pub const SETGRAY: u8 = 0xB8;
+pub const USELUT: u8 = 0xB9;
+pub const PRECHARGELEVEL: u8 = 0xBB;
+pub const VCOMH: u8 = 0xBE;
+
+pub const CONTRASTABC: u8 = 0xC1;
+pub const CONTRASTMASTER: u8 = 0xC7;
+pub const MUXRATIO: u8 = 0xCA;
+pub const COMMANDLOCK: u8 = 0xFD;
+
+/// Init sequences for the SSD1351 modules we've seen
+pub mod presets {
+    use super::*;
+
+    /// From Adafruit's SSD1351 library, for their 1.5" 128x128 module
+    pub const ADAFRUIT: InitSequence = InitSequence::new(&[
+        // unlock everything, some of these commands are locked by default
+        InitCommand::new(COMMANDLOCK, &[0x12]),
+        InitCommand::new(COMMANDLOCK, &[0xb1]),
+        InitCommand::new(DISPLAYOFF, &[]),
+        InitCommand::new(CLOCKDIV, &[0xf1]),
+        InitCommand::new(MUXRATIO, &[127]),
+        InitCommand::new(DISPLAYOFFSET, &[0x00]),
+        InitCommand::new(SETGPIO, &[0x00]),
+        // internal regulator
+        InitCommand::new(FUNCTIONSELECT, &[0x01]),
+        InitCommand::new(PRECHARGE, &[0x32]),
+        InitCommand::new(VCOMH, &[0x05]),
+        InitCommand::new(NORMALDISPLAY, &[]),
+        InitCommand::new(CONTRASTABC, &[0xc8, 0x80, 0xc8]),
+        InitCommand::new(CONTRASTMASTER, &[0x0f]),
+        InitCommand::new(SETVSL, &[0xa0, 0xb5, 0x55]),
+        InitCommand::new(PRECHARGE2, &[0x01]),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::interface::mock::{command, driver, sequence, Event, Log, NoDelay, RST};
+
+    #[test]
+    fn init() {
+        let log = RefCell::new(Log::<256>::new());
+        let mut lcd: Ssd1351<_, _> = driver(&log, PanelConfig::SSD1351, presets::ADAFRUIT);
+        lcd.init(&mut NoDelay).unwrap();
+
+        let expected: Vec<Event> = [
+            vec![Event::Pin(RST, false), Event::Pin(RST, true)],
+            sequence(&presets::ADAFRUIT),
+            // 65k colours, COM split and remapped, RGB
+            command(SETREMAP, &[0x74]),
+            command(STARTLINE, &[0]),
+            command(INVERTDISPLAY, &[]),
+            command(DISPLAYON, &[]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn remap_follows_the_rotation() {
+        for (rotation, remap) in [
+            (Rotation::Deg0, 0x74),
+            (Rotation::Deg90, 0x77),
+            (Rotation::Deg180, 0x66),
+            (Rotation::Deg270, 0x65),
+        ] {
+            let log = RefCell::new(Log::<256>::new());
+            let config = PanelConfig {
+                rotation,
+                ..PanelConfig::SSD1351
+            };
+            driver::<Chip, 256>(&log, config, presets::ADAFRUIT)
+                .init(&mut NoDelay)
+                .unwrap();
+            let configure = [
+                command(SETREMAP, &[remap]),
+                command(STARTLINE, &[0]),
+                command(INVERTDISPLAY, &[]),
+                command(DISPLAYON, &[]),
+            ]
+            .concat();
+            assert!(log.borrow().events().ends_with(&configure), "{rotation:?}");
+        }
+
+        // BGR just drops the RGB bit
+        let config = PanelConfig {
+            color_order: ColorOrder::Bgr,
+            ..PanelConfig::SSD1351
+        };
+        assert_eq!(remap(&config), 0x70);
+    }
+
+    #[test]
+    fn set_window_swaps_x_and_y_on_its_side() {
+        let log = RefCell::new(Log::<32>::new());
+        let mut lcd: Ssd1351<_, _> = driver(&log, PanelConfig::SSD1351, presets::ADAFRUIT);
+        lcd.set_window((10, 20), (30, 40)).unwrap();
+        let expected = [command(SETCOLUMN, &[10, 20]), command(SETROW, &[30, 40])].concat();
+        assert_eq!(log.borrow().events(), expected);
+
+        for rotation in [Rotation::Deg90, Rotation::Deg270] {
+            let log = RefCell::new(Log::<32>::new());
+            let config = PanelConfig {
+                rotation,
+                ..PanelConfig::SSD1351
+            };
+            let mut lcd: Ssd1351<_, _> = driver(&log, config, presets::ADAFRUIT);
+            lcd.set_window((10, 20), (30, 40)).unwrap();
+            let expected = [command(SETCOLUMN, &[30, 40]), command(SETROW, &[10, 20])].concat();
+            assert_eq!(log.borrow().events(), expected);
+        }
+    }
+}
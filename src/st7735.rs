@@ -0,0 +1,158 @@
+use crate::{
+    driver::{Controller, DcsPower, Driver},
+    init_sequence::{InitCommand, InitSequence},
+    interface::ResetTiming,
+};
+
+pub use crate::driver::{
+    CASET, COLMOD, DISPOFF, DISPON, IDMOFF, IDMON, INVOFF, INVON, MADCTL, NOP, NORON, PTLAR, PTLON,
+    RAMRD, RAMWR, RASET, RDDID, RDDST, RDID1, RDID2, RDID3, SLPIN, SLPOUT, SWRESET,
+};
+
+/// An ST7735, with one of the `PanelConfig::ST7735_*` configs for the
+/// module's tab colour, see [`Driver`]
+pub type St7735<DI, Rst> = Driver<Chip, DI, Rst>;
+
+/// What's different about the ST7735
+pub enum Chip {}
+
+impl Controller for Chip {
+    const RESET_TIMING: ResetTiming = RESET_TIMING;
+}
+
+impl DcsPower for Chip {
+    const SLPIN_DELAY_MS: u32 = SLPIN_DELAY_MS;
+    const SLPOUT_DELAY_MS: u32 = SLPOUT_DELAY_MS;
+}
+
+pub const RESET_TIMING: ResetTiming = ResetTiming {
+    pulse_us: 10,
+    recovery_ms: 120,
+};
+
+const SWRESET_DELAY_MS: u32 = 120;
+const SLPIN_DELAY_MS: u32 = 5;
+const SLPOUT_DELAY_MS: u32 = 120;
+
+pub const FRMCTR1: u8 = 0xB1;
+pub const FRMCTR2: u8 = 0xB2;
+pub const FRMCTR3: u8 = 0xB3;
+pub const INVCTR: u8 = 0xB4;
+pub const DISSET5: u8 = 0xB6;
+
+pub const PWCTR1: u8 = 0xC0;
+pub const PWCTR2: u8 = 0xC1;
+pub const PWCTR3: u8 = 0xC2;
+pub const PWCTR4: u8 = 0xC3;
+pub const PWCTR5: u8 = 0xC4;
+pub const VMCTR1: u8 = 0xC5;
+
+pub const RDID4: u8 = 0xDD;
+
+pub const GMCTRP1: u8 = 0xE0;
+pub const GMCTRN1: u8 = 0xE1;
+
+pub const PWCTR6: u8 = 0xFC;
+
+/// Init sequences for the ST7735 modules we've seen
+pub mod presets {
+    use super::*;
+
+    /// From Adafruit's ST7735 library. They have one per tab colour, but
+    /// the differences are all in the window and `MADCTL`, which come from
+    /// the [`crate::panel::PanelConfig`] here.
+    pub const ADAFRUIT: InitSequence = InitSequence::new(&[
+        InitCommand::new(SWRESET, &[]).with_delay(SWRESET_DELAY_MS),
+        InitCommand::new(SLPOUT, &[]).with_delay(SLPOUT_DELAY_MS),
+        // frame rate control -- normal, idle and partial mode
+        InitCommand::new(FRMCTR1, &[0x01, 0x2c, 0x2d]),
+        InitCommand::new(FRMCTR2, &[0x01, 0x2c, 0x2d]),
+        InitCommand::new(FRMCTR3, &[0x01, 0x2c, 0x2d, 0x01, 0x2c, 0x2d]),
+        InitCommand::new(INVCTR, &[0x07]),
+        // power control
+        InitCommand::new(PWCTR1, &[0xa2, 0x02, 0x84]),
+        InitCommand::new(PWCTR2, &[0xc5]),
+        InitCommand::new(PWCTR3, &[0x0a, 0x00]),
+        InitCommand::new(PWCTR4, &[0x8a, 0x2a]),
+        InitCommand::new(PWCTR5, &[0x8a, 0xee]),
+        InitCommand::new(VMCTR1, &[0x0e]),
+        InitCommand::new(COLMOD, &[0x05]),
+        InitCommand::new(
+            GMCTRP1,
+            &[
+                0x02, 0x1c, 0x07, 0x12, //
+                0x37, 0x32, 0x29, 0x2d, //
+                0x29, 0x25, 0x2b, 0x39, //
+                0x00, 0x01, 0x03, 0x10,
+            ],
+        ),
+        InitCommand::new(
+            GMCTRN1,
+            &[
+                0x03, 0x1d, 0x07, 0x06, //
+                0x2e, 0x2c, 0x29, 0x2d, //
+                0x2e, 0x2e, 0x37, 0x3f, //
+                0x00, 0x00, 0x02, 0x10,
+            ],
+        ),
+        InitCommand::new(NORON, &[]),
+        InitCommand::new(DISPON, &[]),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::{
+        interface::mock::{command, driver, sequence, Event, Log, NoDelay, RST},
+        panel::{PanelConfig, Rotation},
+    };
+
+    #[test]
+    fn init() {
+        let log = RefCell::new(Log::<512>::new());
+        let mut lcd: St7735<_, _> = driver(&log, PanelConfig::ST7735_GREEN_TAB, presets::ADAFRUIT);
+        lcd.init(&mut NoDelay).unwrap();
+
+        let expected: Vec<Event> = [
+            vec![Event::Pin(RST, false), Event::Pin(RST, true)],
+            sequence(&presets::ADAFRUIT),
+            command(0x36, &[0x08]),
+            command(0x3a, &[0x55]),
+            command(0x21, &[]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn set_window_adds_the_offset() {
+        let log = RefCell::new(Log::<32>::new());
+        let mut lcd: St7735<_, _> = driver(&log, PanelConfig::ST7735_GREEN_TAB, presets::ADAFRUIT);
+        lcd.set_window((0, 127), (0, 159)).unwrap();
+        let expected = [
+            command(0x2a, &[0, 2, 0, 129]),
+            command(0x2b, &[0, 1, 0, 160]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+
+        // on its side, the 80x160 panel's x offset is counted from the
+        // other end of the 132 rows of RAM
+        let log = RefCell::new(Log::<32>::new());
+        let config = PanelConfig {
+            rotation: Rotation::Deg90,
+            ..PanelConfig::ST7735_80X160
+        };
+        let mut lcd: St7735<_, _> = driver(&log, config, presets::ADAFRUIT);
+        lcd.set_window((0, 159), (0, 79)).unwrap();
+        let expected = [
+            command(0x2a, &[0, 1, 0, 160]),
+            command(0x2b, &[0, 26, 0, 105]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+}
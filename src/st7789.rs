@@ -1,146 +1,235 @@
-use crate::{lcd::{Lcd, Rgb16}, types::OutputPinV2};
-use core::ops::RangeBounds;
-
-use esp32s3_hal::{
-    peripherals::SPI2,
-    prelude::*,
-    spi::{master::Spi, FullDuplexMode},
-    Delay,
+use crate::{
+    driver::{Controller, DcsPower, Driver},
+    init_sequence::{InitCommand, InitSequence},
+    interface::{DisplayInterface, Error, ResetTiming},
+    lcd::{Lcd, Rgb16},
+    pixel_format::{self, PixelFormat},
 };
-use esp_println::println;
 
-pub struct St7789<'a, Dc: OutputPinV2, Bl: OutputPinV2> {
-    pub spi: Spi<'a, SPI2, FullDuplexMode>,
-    pub dc: Dc,
-    pub bl: Bl,
+use core::fmt::Write;
+
+use embedded_hal::digital::OutputPin;
+
+pub use crate::driver::{
+    CASET, COLMOD, DISPOFF, DISPON, IDMOFF, IDMON, INVOFF, INVON, MADCTL, NOP, NORON, PTLAR, PTLON,
+    RAMRD, RAMRDC, RAMWR, RASET, RDDID, RDDST, RDID1, RDID2, RDID3, SLPIN, SLPOUT, SWRESET, TEOFF,
+    TEON,
+};
+
+/// An ST7789, see [`Driver`]
+pub type St7789<DI, Rst> = Driver<Chip, DI, Rst>;
+
+/// What's different about the ST7789
+pub enum Chip {}
+
+impl Controller for Chip {
+    const RESET_TIMING: ResetTiming = RESET_TIMING;
 }
 
-impl<'a, Dc: OutputPinV2, Bl: OutputPinV2> St7789<'a, Dc, Bl> {
-    /// SPI write_bytes
-    /// Logs errors -- because what are we going to do anyway?
-    pub fn write_bytes(&mut self, bytes: &[u8]) {
-        _ = self.spi.write(bytes);
-    }
+impl DcsPower for Chip {
+    const SLPIN_DELAY_MS: u32 = SLPIN_DELAY_MS;
+    const SLPOUT_DELAY_MS: u32 = SLPOUT_DELAY_MS;
+}
 
-    pub fn flush(&mut self) {
-        _ = self.spi.write(&[]);
+impl<DI, Rst> St7789<DI, Rst>
+where
+    DI: DisplayInterface,
+    Rst: OutputPin,
+{
+    /// Turn the TE output on or off. It goes high for each vertical
+    /// blanking period, see [`crate::vsync::TePin`].
+    pub fn set_tearing_effect(&mut self, on: bool) -> Result<(), Error<DI::BusError>> {
+        if on {
+            // vblank only, not hblank as well
+            self.command(TEON, &[0x00])
+        } else {
+            self.command(TEOFF, &[])
+        }
     }
 
-    pub fn write_command(&mut self, command: u8, data: &[u8]) {
-        self.write_commands(&[command]);
-        self.write_data(data);
+    /// The manufacturer, version and module IDs from `RDDID`. Like all the
+    /// reads, this needs a bus that can read, see
+    /// [`DisplayInterface::read_command`].
+    pub fn read_id(&mut self) -> Result<[u8; 3], Error<DI::BusError>> {
+        let mut buf = [0; 4];
+        self.iface.read_command(RDDID, &mut buf)?;
+        Ok(skip_dummy_bit(&buf))
     }
 
-    /// `write_bytes`, but first set command mode
-    pub fn write_commands(&mut self, bytes: &[u8]) {
-        _ = self.dc.set_low();
-        self.write_bytes(bytes);
+    /// What the controller says it's doing, from `RDDST`
+    pub fn read_status(&mut self) -> Result<Status, Error<DI::BusError>> {
+        let mut buf = [0; 5];
+        self.iface.read_command(RDDST, &mut buf)?;
+        Ok(Status(skip_dummy_bit(&buf)))
     }
 
-    /// `write_bytes`, but first set data mode
-    pub fn write_data(&mut self, bytes: &[u8]) {
-        _ = self.dc.set_high();
-        self.write_bytes(bytes);
+    /// Read back what's in the window x and y ranges (inclusive, in rotated
+    /// coordinates), e.g. for a screenshot. The controller sends RGB666
+    /// whatever `COLMOD` is, so we cut it back down to [`Rgb16`].
+    pub fn read_pixels(
+        &mut self,
+        x: (u16, u16),
+        y: (u16, u16),
+        pixels: &mut [Rgb16],
+    ) -> Result<(), Error<DI::BusError>> {
+        self.set_window(x, y)?;
+
+        // a dummy byte, then 3 bytes a pixel
+        let mut buf = [0; 1 + 3 * 32];
+        let mut command = RAMRD;
+        for chunk in pixels.chunks_mut(32) {
+            let buf = &mut buf[..1 + 3 * chunk.len()];
+            self.iface.read_command(command, buf)?;
+            for (pixel, bytes) in chunk.iter_mut().zip(buf[1..].chunks_exact(3)) {
+                *pixel = pixel_format::from_rgb666(bytes);
+            }
+            command = RAMRDC;
+        }
+        Ok(())
     }
 
-    /// Initialize this device, chucks a bunch of magic bytes into the
-    /// peripheral, that I stole from some other pyhton code.
-    pub fn init(&mut self, delay: &mut Delay) {
-        self.write_command(SWRESET, &[]); // software reset
-        delay.delay_ms(300u32); // we have to sleep -- not sure why
-        println!("SWRESET");
+    /// Check that the panel is there and set up the way we asked, and write
+    /// what we find to `log`, e.g. `esp_println::Printer`. This draws a few
+    /// pixels in the top-left corner to read back, so do it before drawing
+    /// anything else. Returns whether everything looked right. It reads
+    /// from the panel, so the bus needs MISO, which is why the firmware only
+    /// runs it with the `self-test` feature.
+    pub fn self_test(&mut self, log: &mut impl Write) -> Result<bool, Error<DI::BusError>> {
+        let mut ok = true;
+
+        let id = self.read_id()?;
+        _ = writeln!(log, "st7789: id {id:02x?}");
+        if id == [0x00; 3] || id == [0xff; 3] {
+            _ = writeln!(log, "st7789: no id, is MISO connected?");
+            ok = false;
+        }
 
-        self.write_command(MADCTL, &[0x70]);
+        let status = self.read_status()?;
+        _ = writeln!(
+            log,
+            "st7789: sleeping {}, display on {}, inverted {}, idle {}, partial {}, \
+             madctl {:#04x}, pixel format {:?}",
+            status.sleeping(),
+            status.display_on(),
+            status.inverted(),
+            status.idle(),
+            status.partial(),
+            status.madctl(),
+            status.pixel_format(),
+        );
+        if status.sleeping() || !status.display_on() {
+            _ = writeln!(log, "st7789: the display isn't on");
+            ok = false;
+        }
+        if status.inverted() != self.config.inverted
+            || status.madctl() != self.config.madctl() & 0xfc
+            || status.pixel_format() != Some(self.config.pixel_format)
+        {
+            _ = writeln!(log, "st7789: the config didn't stick");
+            ok = false;
+        }
 
-        // frame rate control -- idle mode
-        self.write_command(FRMCTR2, &[0x0c, 0x0c, 0x00, 0x33, 0x33]);
-        self.write_command(COLMOD, &[0x05]);
-        self.write_command(GCTRL, &[0x14]);
-        self.write_command(VCOMS, &[0x37]);
+        let pattern = [
+            Rgb16::BLACK,
+            Rgb16::WHITE,
+            Rgb16::from_rgb(255, 0, 0),
+            Rgb16::from_rgb(0, 0, 255),
+        ];
+        self.prepare_window((0, 3), (0, 0))?;
+        self.write_rgb(&pattern)?;
+        let mut read = [Rgb16::BLACK; 4];
+        self.read_pixels((0, 3), (0, 0), &mut read)?;
+        if read != pattern {
+            _ = writeln!(
+                log,
+                "st7789: wrote {pattern:?} to RAM but read back {read:?}"
+            );
+            ok = false;
+        }
 
-        // power control
-        self.write_command(LCMCTRL, &[0x2c]);
-        self.write_command(VDVVRHEN, &[0x01]);
-        self.write_command(VRHS, &[0x12]);
-        self.write_command(VDVS, &[0x20]);
+        _ = writeln!(
+            log,
+            "st7789: self-test {}",
+            if ok { "passed" } else { "failed" }
+        );
+        Ok(ok)
+    }
 
-        self.write_command(0xd0, &[0xa4, 0xa1]);
-        self.write_command(FRCTRL2, &[0x0f]);
+    pub fn write_pixels(&mut self, pixels: &[u8]) -> Result<(), Error<DI::BusError>> {
+        let (width, height) = self.config.dimensions();
+        self.set_window((0, width - 1), (0, height - 1))?;
+        self.iface.write_command(RAMWR, pixels)
+    }
+}
 
-        // set gamma
-        self.write_command(
-            GMCTRP1,
-            &[
-                0xd0, 0x04, 0x0d, 0x11, // not sure
-                0x13, 0x2b, 0x3f, 0x54, // what this
-                0x4c, 0x18, 0x0d, 0x0b, // does (comments are for rustfmt)
-                0x1f, 0x23,
-            ],
-        );
+/// `RDDID` and `RDDST` start with a single dummy bit, so everything we read
+/// is a bit late
+fn skip_dummy_bit<const N: usize>(buf: &[u8]) -> [u8; N] {
+    core::array::from_fn(|i| buf[i] << 1 | buf[i + 1] >> 7)
+}
 
-        // set gamma
-        self.write_command(
-            GMCTRN1,
-            &[
-                0xd0, 0x04, 0x0c, 0x11, //
-                0x13, 0x2c, 0x3f, 0x44, //
-                0x51, 0x2f, 0x1f, 0x1f, //
-                0x20, 0x23,
-            ],
-        );
+/// What `RDDST` says, which is the same on every MIPI-style controller
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Status(pub [u8; 4]);
 
-        self.write_command(SLPOUT, &[]);
-        self.write_command(DISPON, &[]);
-        println!("DISPON");
-        delay.delay_ms(300u32);
+impl Status {
+    fn bit(&self, n: u32) -> bool {
+        u32::from_be_bytes(self.0) >> n & 1 != 0
     }
 
-    pub fn set_bl_high(&mut self) {
-        _ = self.bl.set_high();
+    pub fn booster_on(&self) -> bool {
+        self.bit(31)
     }
 
-    /// Set the window x and y ranges, e.g.
-    /// ```no_run
-    /// lcd.set_window((0, 240), (0, 240));
-    /// ```
-    pub fn set_window(&mut self, x: (u16, u16), y: (u16, u16)) {
-        fn b(n: u16) -> (u8, u8) {
-            ((n >> 8) as u8, n as u8)
+    /// The rotation and colour order bits, in the same places as in
+    /// `MADCTL`
+    pub fn madctl(&self) -> u8 {
+        self.0[0] << 1 & 0xfc
+    }
+
+    /// `None` if it's set to something we don't use
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        match self.0[1] >> 4 & 0b111 {
+            0b101 => Some(PixelFormat::Rgb565),
+            0b110 => Some(PixelFormat::Rgb666),
+            0b011 => Some(PixelFormat::Rgb444),
+            _ => None,
         }
+    }
 
-        self.write_command(CASET, &[b(x.0).0, b(x.0).1, b(x.1).0, b(x.1).1]);
-        self.write_command(RASET, &[b(y.0).0, b(y.0).1, b(y.1).0, b(y.1).1]);
+    pub fn idle(&self) -> bool {
+        self.bit(19)
     }
 
-    pub fn write_pixels(&mut self, pixels: &[u8]) {
-        self.set_window((0, 240), (0, 240));
-        self.write_command(RAMWR, pixels);
+    pub fn partial(&self) -> bool {
+        self.bit(18)
     }
-}
 
-pub const NOP: u8 = 0x00;
-pub const SWRESET: u8 = 0x01;
-pub const RDDID: u8 = 0x04;
-pub const RDDST: u8 = 0x09;
+    pub fn sleeping(&self) -> bool {
+        !self.bit(17)
+    }
 
-pub const SLPIN: u8 = 0x10;
-pub const SLPOUT: u8 = 0x11;
-pub const PTLON: u8 = 0x12;
-pub const NORON: u8 = 0x13;
+    pub fn inverted(&self) -> bool {
+        self.bit(13)
+    }
 
-pub const INVOFF: u8 = 0x20;
-pub const INVON: u8 = 0x21;
-pub const DISPOFF: u8 = 0x28;
-pub const DISPON: u8 = 0x29;
+    pub fn display_on(&self) -> bool {
+        self.bit(10)
+    }
+
+    pub fn tearing_effect(&self) -> bool {
+        self.bit(9)
+    }
+}
 
-pub const CASET: u8 = 0x2A;
-pub const RASET: u8 = 0x2B;
-pub const RAMWR: u8 = 0x2C;
-pub const RAMRD: u8 = 0x2E;
+pub const RESET_TIMING: ResetTiming = ResetTiming {
+    pulse_us: 10,
+    recovery_ms: 120,
+};
 
-pub const PTLAR: u8 = 0x30;
-pub const MADCTL: u8 = 0x36;
-pub const COLMOD: u8 = 0x3A;
+const SWRESET_DELAY_MS: u32 = 120;
+const SLPIN_DELAY_MS: u32 = 5;
+const SLPOUT_DELAY_MS: u32 = 5;
 
 pub const FRMCTR1: u8 = 0xB1;
 pub const FRMCTR2: u8 = 0xB2;
@@ -161,9 +250,8 @@ pub const VMCTR1: u8 = 0xC5;
 pub const FRCTRL2: u8 = 0xC6;
 pub const CABCCTRL: u8 = 0xC7;
 
-pub const RDID1: u8 = 0xDA;
-pub const RDID2: u8 = 0xDB;
-pub const RDID3: u8 = 0xDC;
+pub const PWCTRL1: u8 = 0xD0;
+
 pub const RDID4: u8 = 0xDD;
 
 pub const GMCTRP1: u8 = 0xE0;
@@ -171,17 +259,251 @@ pub const GMCTRN1: u8 = 0xE1;
 
 pub const PWCTR6: u8 = 0xFC;
 
-impl<'a, Dc, Bl> Lcd for St7789<'a, Dc, Bl>
-where
-    Dc: OutputPinV2,
-    Bl: OutputPinV2,
-{
-    fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) {
-        self.set_window(x, y);
-        self.write_commands(&[RAMWR]);
+/// Init sequences for the ST7789 modules we've seen
+pub mod presets {
+    use super::*;
+
+    /// From Pimoroni's python library -- tuned for their 240x240 IPS module,
+    /// but it's what we have been using on everything
+    pub const PIMORONI: InitSequence = InitSequence::new(&[
+        InitCommand::new(SWRESET, &[]).with_delay(SWRESET_DELAY_MS),
+        // frame rate control -- idle mode
+        InitCommand::new(FRMCTR2, &[0x0c, 0x0c, 0x00, 0x33, 0x33]),
+        InitCommand::new(COLMOD, &[0x05]),
+        InitCommand::new(GCTRL, &[0x14]),
+        InitCommand::new(VCOMS, &[0x37]),
+        // power control
+        InitCommand::new(LCMCTRL, &[0x2c]),
+        InitCommand::new(VDVVRHEN, &[0x01]),
+        InitCommand::new(VRHS, &[0x12]),
+        InitCommand::new(VDVS, &[0x20]),
+        InitCommand::new(PWCTRL1, &[0xa4, 0xa1]),
+        InitCommand::new(FRCTRL2, &[0x0f]),
+        // set gamma
+        InitCommand::new(
+            GMCTRP1,
+            &[
+                0xd0, 0x04, 0x0d, 0x11, // not sure
+                0x13, 0x2b, 0x3f, 0x54, // what this
+                0x4c, 0x18, 0x0d, 0x0b, // does (comments are for rustfmt)
+                0x1f, 0x23,
+            ],
+        ),
+        InitCommand::new(
+            GMCTRN1,
+            &[
+                0xd0, 0x04, 0x0c, 0x11, //
+                0x13, 0x2c, 0x3f, 0x44, //
+                0x51, 0x2f, 0x1f, 0x1f, //
+                0x20, 0x23,
+            ],
+        ),
+        InitCommand::new(SLPOUT, &[]).with_delay(SLPOUT_DELAY_MS),
+        InitCommand::new(DISPON, &[]),
+    ]);
+
+    /// Waveshare's sequence for their 1.14" (135x240) and 1.3" (240x240)
+    /// modules. The same as [`PIMORONI`] but for the gate and VCOM voltages.
+    pub const WAVESHARE: InitSequence = PIMORONI.patched(&[
+        InitCommand::new(GCTRL, &[0x35]),
+        InitCommand::new(VCOMS, &[0x19]),
+    ]);
+
+    /// Adafruit's minimal sequence, which leaves everything else at the
+    /// power-on defaults. A good first thing to try on an unknown module.
+    pub const ADAFRUIT: InitSequence = InitSequence::new(&[
+        InitCommand::new(SWRESET, &[]).with_delay(SWRESET_DELAY_MS),
+        InitCommand::new(SLPOUT, &[]).with_delay(SLPOUT_DELAY_MS),
+        InitCommand::new(COLMOD, &[0x55]),
+        InitCommand::new(NORON, &[]),
+        InitCommand::new(DISPON, &[]),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use super::*;
+    use crate::{
+        interface::mock::{
+            command, driver, Event, Log, MockDelay, MockPin, MockSpiInterface, NoDelay, DC, RST,
+        },
+        lcd::{Lcd, PowerManagement, Rgb16},
+        panel::{PanelConfig, Rotation},
+        pixel_format::PixelFormat,
+    };
+
+    type MockSt7789<'a> = St7789<MockSpiInterface<'a, 256>, MockPin<'a, 256>>;
+
+    fn lcd(log: &RefCell<Log<256>>, config: PanelConfig) -> MockSt7789<'_> {
+        driver(log, config, presets::ADAFRUIT)
+    }
+
+    #[test]
+    fn init() {
+        let log = RefCell::new(Log::new());
+        let config = PanelConfig {
+            rotation: Rotation::Deg90,
+            inverted: true,
+            ..PanelConfig::P240X240
+        };
+        lcd(&log, config).init(&mut NoDelay).unwrap();
+
+        let expected: Vec<Event> = [
+            vec![Event::Pin(RST, false), Event::Pin(RST, true)],
+            command(0x01, &[]),
+            command(0x11, &[]),
+            command(0x3a, &[0x55]),
+            command(0x13, &[]),
+            command(0x29, &[]),
+            command(0x36, &[0x60]),
+            command(0x3a, &[0x55]),
+            command(0x21, &[]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn reinit_replays_the_init_sequence() {
+        let log = RefCell::new(Log::new());
+        let mut lcd = lcd(&log, PanelConfig::P240X240);
+        lcd.init(&mut MockDelay(&log)).unwrap();
+        let init = log.borrow().events().to_vec();
+        // the reset pulse, and the waits after it, `SWRESET` and `SLPOUT`
+        assert_eq!(
+            init[..4],
+            [
+                Event::Pin(RST, false),
+                Event::Delay(10),
+                Event::Pin(RST, true),
+                Event::Delay(120_000),
+            ]
+        );
+        assert!(init.contains(&Event::Delay(SWRESET_DELAY_MS * 1000)));
+        assert!(init.contains(&Event::Delay(SLPOUT_DELAY_MS * 1000)));
+
+        log.borrow_mut().clear();
+        lcd.reinit(&mut MockDelay(&log)).unwrap();
+        assert_eq!(log.borrow().events(), init);
+    }
+
+    #[test]
+    fn partial_rows_add_the_offset() {
+        let log = RefCell::new(Log::new());
+        let mut lcd = lcd(&log, PanelConfig::P135X240);
+        lcd.set_partial(Some((10, 300))).unwrap();
+        lcd.set_partial(None).unwrap();
+
+        let expected = [
+            command(0x30, &[0x00, 0x32, 0x01, 0x54]),
+            command(0x12, &[]),
+            command(0x13, &[]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn sleep_and_wake_wait_afterwards() {
+        let log = RefCell::new(Log::new());
+        let mut lcd = lcd(&log, PanelConfig::P240X240);
+        lcd.sleep(&mut MockDelay(&log)).unwrap();
+        lcd.wake(&mut MockDelay(&log)).unwrap();
+
+        let expected = [
+            command(0x10, &[]),
+            vec![Event::Delay(SLPIN_DELAY_MS * 1000)],
+            command(0x11, &[]),
+            vec![Event::Delay(SLPOUT_DELAY_MS * 1000)],
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn set_window_adds_the_offset() {
+        let log = RefCell::new(Log::new());
+        lcd(&log, PanelConfig::P135X240)
+            .set_window((0, 134), (0, 239))
+            .unwrap();
+
+        let expected = [
+            command(0x2a, &[0x00, 0x34, 0x00, 0xba]),
+            command(0x2b, &[0x00, 0x28, 0x01, 0x17]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn fill_rect_is_clipped_to_the_display() {
+        let log = RefCell::new(Log::new());
+        let mut lcd = lcd(&log, PanelConfig::P240X240);
+        lcd.fill_rect(u16::MAX, 0, 2, 2, Rgb16::BLACK).unwrap();
+        assert_eq!(log.borrow().events(), []);
+
+        lcd.fill_rect(238, 239, 10, 10, Rgb16::BLACK).unwrap();
+        let expected = [
+            command(0x2a, &[0, 238, 0, 239]),
+            command(0x2b, &[0, 239, 0, 239]),
+            command(0x2c, &[]),
+            // two pixels, and black is all ones
+            vec![Event::Write(0xff); 4],
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
+    }
+
+    #[test]
+    fn tearing_effect_comes_after_a_pending_pixel() {
+        let log = RefCell::new(Log::new());
+        let config = PanelConfig {
+            pixel_format: PixelFormat::Rgb444,
+            ..PanelConfig::P240X240
+        };
+        let mut lcd = lcd(&log, config);
+        lcd.prepare_window((0, 0), (0, 0)).unwrap();
+        lcd.write_rgb(&[Rgb16::BLACK]).unwrap();
+        log.borrow_mut().clear();
+
+        lcd.set_tearing_effect(true).unwrap();
+        let expected = [
+            // the pixel padded out to 2 bytes
+            vec![Event::Pin(DC, true), Event::Write(0xff), Event::Write(0xf0)],
+            command(0x35, &[0x00]),
+        ]
+        .concat();
+        assert_eq!(log.borrow().events(), expected);
     }
 
-    fn write_rgb(&mut self, rgb: &[Rgb16]) {
-        self.write_data(Rgb16::as_bytes(rgb));
+    #[test]
+    fn status_and_id_skip_the_dummy_bit() {
+        // what an ST7789 sends after `presets::ADAFRUIT` in landscape, with
+        // TE on. The dummy bit and whatever comes after the last bit are
+        // set, to check they're dropped.
+        let status = Status(skip_dummy_bit(&[0xd8, 0x29, 0x93, 0x00, 0x55]));
+        assert_eq!(status, Status([0xb0, 0x53, 0x26, 0x00]));
+        assert!(status.booster_on());
+        assert_eq!(status.madctl(), 0x60);
+        assert_eq!(status.pixel_format(), Some(PixelFormat::Rgb565));
+        assert!(!status.idle());
+        assert!(!status.partial());
+        assert!(!status.sleeping());
+        assert!(status.inverted());
+        assert!(status.display_on());
+        assert!(status.tearing_effect());
+
+        // straight after a reset, everything is off and it's in RGB666
+        let status = Status(skip_dummy_bit(&[0x80, 0x30, 0x00, 0x00, 0x00]));
+        assert!(!status.booster_on());
+        assert_eq!(status.pixel_format(), Some(PixelFormat::Rgb666));
+        assert!(status.sleeping());
+        assert!(!status.display_on());
+
+        // the manufacturer, version and module IDs
+        let id: [u8; 3] = skip_dummy_bit(&[0xc2, 0xc2, 0xa9, 0x2a]);
+        assert_eq!(id, [0x85, 0x85, 0x52]);
     }
 }
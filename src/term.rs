@@ -1,22 +1,17 @@
-use core::ops::RangeBounds;
+use core::iter;
+use core::ops::Range;
 
-use esp32s3_hal::Delay;
-use esp_println::println;
-
-use crate::lcd::Lcd;
-use crate::{lazy_spinlock::LazySpinlock, lcd::Rgb16};
-
-macro_rules! include_rgb565 {
-    ($path:expr) => {{
-        let bytes = include_bytes!($path);
-        Rgb16::from_bytes(bytes)
-    }};
-}
+use crate::executor;
+use crate::font::{Font, FONT};
+use crate::image::Image;
+use crate::lcd::{AsyncLcd, Lcd, Rect, Rgb16};
+use crate::vsync::VSync;
 
 pub const CHAR_HEIGHT: usize = 16;
 pub const CHAR_WIDTH: usize = 8;
 
-pub static FONT: &[u8] = include_bytes!("./image/font.bmi");
+/// How much of a glyph row we draw at once, wider rows are split up
+pub const MAX_GLYPH_WIDTH: usize = 32;
 
 /// Represents a single character on the terminal, a character has a
 /// background and a foreground color, as well as a value.
@@ -29,6 +24,19 @@ pub struct Char {
 }
 
 impl Char {
+    /// A cell that belongs to something else (e.g. an [`Image`]), so the
+    /// terminal should never draw over it. `NUL` has no glyph, so we use
+    /// that as the marker.
+    pub const RESERVED: Self = Self {
+        value: 0,
+        foreground: Rgb16::WHITE,
+        background: Rgb16::BLACK,
+    };
+
+    pub fn is_reserved(&self) -> bool {
+        self.value() == 0
+    }
+
     pub fn is_flushed(&self) -> bool {
         (self.value & 0b1000_0000) == 0
     }
@@ -45,35 +53,133 @@ impl Char {
         self.value & 0b0111_1111
     }
 
-    /// Get a bit array of pixels reperesenting this letter
-    fn get_letter_bits(&self) -> &[u8] {
-        let letter_size: usize = CHAR_HEIGHT;
-        let start = (self.value() - b' ') as usize * letter_size;
-        &FONT[start..start + letter_size]
+    /// The colour of a pixel with this much of the glyph over it. Partial
+    /// coverage (from grayscale fonts) is blended towards the foreground.
+    fn shade(&self, coverage: u8) -> Rgb16 {
+        match coverage {
+            0 => self.background,
+            255 => self.foreground,
+            coverage => self.background.lerp(self.foreground, coverage),
+        }
     }
 
-    fn get_letter_pixels(
+    /// Draw the `cols` of each of the `rows` of this char's glyph into
+    /// `letter`
+    pub(crate) fn get_glyph_pixels(
         &self,
+        font: &Font,
         letter: &mut [Rgb16],
         cols: impl IntoIterator<Item = usize> + Clone,
+        rows: impl IntoIterator<Item = usize>,
     ) {
-        let letter_bits = self.get_letter_bits();
+        let glyph = font.glyph(self.value());
         let mut i = 0;
-        // this only works because a letter is a byte wide
-        for &row in letter_bits.iter() {
-            for offset in cols.clone() {
-                if ((0b1000_0000 >> offset) & row) != 0 {
-                    letter[i] = self.foreground;
-                }
+        for row in rows {
+            for col in cols.clone() {
+                letter[i] = self.shade(glyph.coverage(col, row));
                 i += 1;
             }
         }
     }
 
-    pub fn display(&self, lcd: &mut impl Lcd) {
-        let mut letter = [self.background; 8 * 16];
-        self.get_letter_pixels(&mut letter, 0..8);
-        lcd.write_rgb(&letter);
+    /// Draw the whole glyph into a `CHAR_WIDTH` by `CHAR_HEIGHT` window
+    pub fn display<L: Lcd>(&self, lcd: &mut L) -> Result<(), L::Error> {
+        let glyph = Pixels::Glyph {
+            ch: *self,
+            cols: 0..CHAR_WIDTH,
+            rows: 0..CHAR_HEIGHT,
+            scale: (1, 1),
+        };
+        lcd.write_rgb_with(CHAR_WIDTH * CHAR_HEIGHT, &mut |start, pixels| {
+            glyph.rasterise(start, pixels)
+        })
+    }
+
+    /// Like [`Char::display`], but with any font. The window should already
+    /// be `font.width` by `font.height`. Glyphs are written a row at a time
+    /// (rows wider than [`MAX_GLYPH_WIDTH`] a piece at a time), so this
+    /// works for fonts bigger than a cell.
+    pub fn display_with<L: Lcd>(&self, font: &Font, lcd: &mut L) -> Result<(), L::Error> {
+        for row in 0..font.height {
+            for start in (0..font.width).step_by(MAX_GLYPH_WIDTH) {
+                let cols = start..(start + MAX_GLYPH_WIDTH).min(font.width);
+                let mut line = [self.background; MAX_GLYPH_WIDTH];
+                self.get_glyph_pixels(font, &mut line, cols.clone(), row..row + 1);
+                lcd.write_rgb(&line[..cols.len()])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What goes in a [`Window`], which is drawn a piece at a time with
+/// [`Lcd::write_rgb_with`], so it's never all in memory at once
+#[derive(Clone)]
+pub enum Pixels {
+    /// every pixel the same
+    Solid(Rgb16),
+    /// the `cols` of the `rows` of a char's glyph, with each pixel blown up
+    /// to `scale`
+    Glyph {
+        ch: Char,
+        cols: Range<usize>,
+        rows: Range<usize>,
+        scale: (usize, usize),
+    },
+}
+
+impl Pixels {
+    /// Draw pixels `start..start + pixels.len()` of the window, left to right
+    /// and then top to bottom
+    pub fn rasterise(&self, start: usize, pixels: &mut [Rgb16]) {
+        match self {
+            Pixels::Solid(colour) => pixels.fill(*colour),
+            Pixels::Glyph {
+                ch,
+                cols,
+                rows,
+                scale: (scale_x, scale_y),
+            } => {
+                let glyph = FONT.glyph(ch.value());
+                let width = cols.len() * scale_x;
+                for (i, pixel) in (start..).zip(pixels) {
+                    let col = cols.start + i % width / scale_x;
+                    let row = rows.start + i / width / scale_y;
+                    *pixel = ch.shade(glyph.coverage(col, row));
+                }
+            }
+        }
+    }
+}
+
+/// A window (inclusive, like [`Lcd::prepare_window`]) and what goes in it.
+/// [`Term`] and [`ScrollableRow`] work out what to draw as these, so that
+/// drawing to an [`Lcd`] and to an [`AsyncLcd`] only differ in how they're
+/// sent.
+#[derive(Clone)]
+pub struct Window {
+    pub x: (u16, u16),
+    pub y: (u16, u16),
+    pub pixels: Pixels,
+}
+
+impl Window {
+    fn count(&self) -> usize {
+        (self.x.1 - self.x.0 + 1) as usize * (self.y.1 - self.y.0 + 1) as usize
+    }
+
+    pub fn draw<L: Lcd>(&self, lcd: &mut L) -> Result<(), L::Error> {
+        lcd.prepare_window(self.x, self.y)?;
+        lcd.write_rgb_with(self.count(), &mut |start, pixels| {
+            self.pixels.rasterise(start, pixels)
+        })
+    }
+
+    /// [`Window::draw`] for an [`AsyncLcd`]
+    pub async fn draw_async<L: AsyncLcd>(&self, lcd: &mut L) -> Result<(), L::Error> {
+        lcd.prepare_window(self.x, self.y).await?;
+        let mut fill = |start, pixels: &mut [Rgb16]| self.pixels.rasterise(start, pixels);
+        lcd.write_rgb_with(self.count(), &mut fill).await
     }
 }
 
@@ -87,49 +193,399 @@ impl Default for Char {
     }
 }
 
+/// How a row of the terminal is drawn, like the VT100's DECDWL/DECDHL line
+/// attributes. Double-height rows are double-width too, and come in pairs:
+/// write the same text to both, with the top half above the bottom half.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum LineSize {
+    #[default]
+    Single,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+impl LineSize {
+    /// `(x, y)` scale that glyphs in this row are drawn at
+    pub fn scale(self) -> (usize, usize) {
+        match self {
+            LineSize::Single => (1, 1),
+            LineSize::DoubleWidth => (2, 1),
+            LineSize::DoubleHeightTop | LineSize::DoubleHeightBottom => (2, 2),
+        }
+    }
+
+    /// Which rows of each glyph are drawn in this row
+    pub fn glyph_rows(self) -> Range<usize> {
+        match self {
+            LineSize::Single | LineSize::DoubleWidth => 0..CHAR_HEIGHT,
+            LineSize::DoubleHeightTop => 0..CHAR_HEIGHT / 2,
+            LineSize::DoubleHeightBottom => CHAR_HEIGHT / 2..CHAR_HEIGHT,
+        }
+    }
+
+    /// How many of a row's `width` cells actually fit on the display
+    pub fn visible_cells(self, width: usize) -> usize {
+        width / self.scale().0
+    }
+}
+
+/// How many `(columns, rows)` of cells fit on a display of `dimensions`
+/// when every glyph is `scale` times bigger. It's `const`, so it can size a
+/// [`Term`] for whatever panel we're built for:
+/// ```no_run
+/// const PANEL: PanelConfig = PanelConfig::ST7735_80X160;
+/// const SIZE: (usize, usize) = term::fit(PANEL.dimensions(), 1);
+/// let mut term = Term::<{ SIZE.0 }, { SIZE.1 }>::new();
+/// ```
+pub const fn fit((width, height): (u16, u16), scale: usize) -> (usize, usize) {
+    (
+        width as usize / (CHAR_WIDTH * scale),
+        height as usize / (CHAR_HEIGHT * scale),
+    )
+}
+
 pub struct Term<const WIDTH: usize, const HEIGHT: usize> {
     cells: [[Char; WIDTH]; HEIGHT],
+    line_sizes: [LineSize; HEIGHT],
+    /// every glyph is drawn this many times bigger, on top of the line size
+    scale: usize,
+    /// rows that got narrower than the terminal (an odd `WIDTH` that went
+    /// double width), whose last few columns of pixels need clearing
+    stale_tails: [bool; HEIGHT],
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Default for Term<WIDTH, HEIGHT> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<const WIDTH: usize, const HEIGHT: usize> Term<WIDTH, HEIGHT> {
     pub fn new() -> Self {
+        Self::with_scale(1)
+    }
+
+    /// A terminal whose cells are `scale` times bigger than the font, e.g. a
+    /// `Term::<15, 7>::with_scale(2)` fills most of a 240x240 display.
+    /// Panics if `scale` is 0.
+    pub fn with_scale(scale: usize) -> Self {
+        assert!(scale != 0);
         Self {
             cells: [[Char::default(); WIDTH]; HEIGHT],
+            line_sizes: [LineSize::Single; HEIGHT],
+            scale,
+            stale_tails: [false; HEIGHT],
+        }
+    }
+
+    pub fn scale(&self) -> usize {
+        self.scale
+    }
+
+    /// Change how `row` is drawn. The whole row is redrawn on the next
+    /// [`Term::display`]. Only the first [`LineSize::visible_cells`] of a
+    /// scaled row are drawn, the rest are kept for when it goes back to
+    /// [`LineSize::Single`].
+    pub fn set_line_size(&mut self, row: usize, size: LineSize) {
+        if self.line_sizes[row] == size {
+            return;
+        }
+        self.line_sizes[row] = size;
+        self.stale_tails[row] = self.tail_rect(row).is_some();
+        for ch in self.cells[row].iter_mut() {
+            if !ch.is_reserved() {
+                ch.mark_clogged();
+            }
         }
     }
 
+    pub fn line_size(&self, row: usize) -> LineSize {
+        self.line_sizes[row]
+    }
+
+    /// Set the char at `coords`, does nothing if the cell is reserved
     pub fn set_char(&mut self, coords: (usize, usize), mut ch: Char) {
+        let cell = &mut self.cells[coords.1][coords.0];
+        if cell.is_reserved() {
+            return;
+        }
         ch.mark_clogged();
-        self.cells[coords.1][coords.0] = ch;
+        *cell = ch;
     }
 
     pub fn set_row_chars(&mut self, row: usize, s: &[u8]) {
         for (&s, c) in s.iter().zip(self.cells[row].iter_mut()) {
+            if c.is_reserved() {
+                continue;
+            }
             c.value = s;
             c.mark_clogged();
         }
     }
 
-    pub fn display_immediately(lcd: &mut impl Lcd, (x, y): (usize, usize), mut ch: Char) {
-        ch.mark_flushed();
-        lcd.prepare_window(
-            ((x * 8) as u16, (x * 8 + 7) as u16),
-            ((y * 16) as u16, (y * 16 + 15) as u16),
-        );
-        ch.display(lcd);
+    /// Whether anything has changed since the last [`Term::display`], e.g.
+    /// to dim the backlight when nothing has for a while
+    pub fn is_dirty(&self) -> bool {
+        // cells past the end of a scaled row stay dirty, but they aren't
+        // drawn until it goes back to single width
+        let stale_tail = self.stale_tails.iter().any(|&stale| stale);
+        stale_tail
+            || self.cells.iter().zip(self.line_sizes).any(|(row, size)| {
+                row[..size.visible_cells(WIDTH)]
+                    .iter()
+                    .any(|ch| !ch.is_flushed() && !ch.is_reserved())
+            })
     }
 
-    pub fn display(&mut self, lcd: &mut impl Lcd) {
-        for (i, row) in self.cells.iter_mut().enumerate() {
-            for (j, ch) in row.iter_mut().enumerate() {
-                if ch.is_flushed() {
-                    continue;
-                }
-                Self::display_immediately(lcd, (j, i), *ch);
-                ch.mark_flushed();
+    /// Mark every cell as changed, so the next [`Term::display`] redraws the
+    /// whole terminal, e.g. after the display has been reinitialised.
+    /// Reserved cells are left alone, whatever is in them needs redrawing
+    /// separately.
+    pub fn invalidate(&mut self) {
+        for ch in self.cells.iter_mut().flatten() {
+            if !ch.is_reserved() {
+                ch.mark_clogged();
+            }
+        }
+    }
+
+    /// Iterate over the cells in a `(width, height)` rectangle starting at
+    /// `(x, y)`, clipped to the terminal
+    fn cells_in(
+        &mut self,
+        (x, y): (usize, usize),
+        (width, height): (usize, usize),
+    ) -> impl Iterator<Item = &mut Char> {
+        let cols = x.min(WIDTH)..(x + width).min(WIDTH);
+        let rows = y.min(HEIGHT)..(y + height).min(HEIGHT);
+        self.cells[rows]
+            .iter_mut()
+            .flat_map(move |row| row[cols.clone()].iter_mut())
+    }
+
+    /// Reserve a `(width, height)` rectangle of cells starting at `(x, y)`.
+    /// [`Term::display`] will skip these cells, and writes to them are
+    /// ignored, until they are [`Term::release`]d.
+    pub fn reserve(&mut self, coords: (usize, usize), size: (usize, usize)) {
+        for ch in self.cells_in(coords, size) {
+            *ch = Char::RESERVED;
+        }
+    }
+
+    /// Give reserved cells back to the terminal. They are cleared to spaces
+    /// and will be drawn on the next [`Term::display`].
+    pub fn release(&mut self, coords: (usize, usize), size: (usize, usize)) {
+        for ch in self.cells_in(coords, size) {
+            if ch.is_reserved() {
+                *ch = Char::default();
+            }
+        }
+    }
+
+    /// Draw `image` with its top-left corner at the cell `(x, y)`, reserving
+    /// every cell that it covers (even partially) so that it isn't drawn over.
+    /// Cells are as wide as their row's [`LineSize`] makes them, so an image
+    /// can cover a different number of cells in each row. Panics if `y` isn't
+    /// a row of the terminal.
+    pub fn display_image<L: Lcd>(
+        &mut self,
+        lcd: &mut L,
+        (x, y): (usize, usize),
+        image: &Image,
+    ) -> Result<(), L::Error> {
+        let origin = self.cell_rect((x, y));
+        let left = origin.x as usize;
+        let right = left + image.width as usize;
+        let rows = (image.height as usize).div_ceil(origin.height as usize);
+        for row in y..(y + rows).min(HEIGHT) {
+            let cell_width = self.cell_rect((0, row)).width as usize;
+            let cols = left / cell_width..right.div_ceil(cell_width);
+            self.reserve((cols.start, row), (cols.len(), 1));
+        }
+        image.draw(lcd, (origin.x as i32, origin.y as i32))
+    }
+
+    /// The pixels that the cell `(x, y)` covers, given its row's size
+    pub fn cell_rect(&self, (x, y): (usize, usize)) -> Rect {
+        let scale_x = self.line_sizes[y].scale().0 * self.scale;
+        let width = CHAR_WIDTH * scale_x;
+        let height = CHAR_HEIGHT * self.scale;
+        Rect::new(
+            (x * width) as u16,
+            (y * height) as u16,
+            width as u16,
+            height as u16,
+        )
+    }
+
+    /// The pixels at the end of `row` that none of its visible cells cover,
+    /// if there are any. This only happens when the row is scaled and
+    /// `WIDTH` doesn't divide evenly.
+    fn tail_rect(&self, row: usize) -> Option<Rect> {
+        let size = self.line_sizes[row];
+        let start = self.cell_rect((size.visible_cells(WIDTH), row)).x;
+        let end = (WIDTH * CHAR_WIDTH * self.scale) as u16;
+        let rect = self.cell_rect((0, row));
+        Some(Rect::new(start, rect.y, end - start, rect.height)).filter(|rect| !rect.is_empty())
+    }
+
+    /// The [`Term::tail_rect`] of `row` if it still needs clearing
+    fn stale_tail(&self, row: usize) -> Option<Rect> {
+        self.stale_tails[row].then(|| self.tail_rect(row)).flatten()
+    }
+
+    /// Whether all of the cell `(x, y)` is in `lcd`'s [`Lcd::safe_area`],
+    /// e.g. not in the corners of a round display
+    pub fn is_cell_visible<L: Lcd>(&self, lcd: &L, coords: (usize, usize)) -> bool {
+        let size = self.line_sizes[coords.1];
+        coords.0 < size.visible_cells(WIDTH)
+            && lcd
+                .safe_area()
+                .contains(lcd.dimensions(), &self.cell_rect(coords))
+    }
+
+    /// The cells of `row` that are completely visible, see
+    /// [`Term::is_cell_visible`]. The safe area has no holes in it, so this
+    /// is a single range, which might be empty for rows near the top and
+    /// bottom of a round display.
+    pub fn visible_columns<L: Lcd>(&self, lcd: &L, row: usize) -> Range<usize> {
+        let width = self.line_sizes[row].visible_cells(WIDTH);
+        let start = (0..width)
+            .find(|&x| self.is_cell_visible(lcd, (x, row)))
+            .unwrap_or(width);
+        let end = (start..width)
+            .find(|&x| !self.is_cell_visible(lcd, (x, row)))
+            .unwrap_or(width);
+        start..end
+    }
+
+    pub fn display_immediately<L: Lcd>(
+        lcd: &mut L,
+        coords: (usize, usize),
+        ch: Char,
+    ) -> Result<(), L::Error> {
+        Self::display_immediately_scaled(lcd, coords, ch, LineSize::Single, 1)
+    }
+
+    /// [`Term::display_immediately`] for a cell in a row of the given size,
+    /// in a terminal with the given scale. `(x, y)` is still a cell
+    /// position, so in a double-width row `x` should be less than
+    /// `WIDTH / 2`.
+    pub fn display_immediately_scaled<L: Lcd>(
+        lcd: &mut L,
+        coords: (usize, usize),
+        ch: Char,
+        size: LineSize,
+        scale: usize,
+    ) -> Result<(), L::Error> {
+        Self::cell_window(coords, ch, size, scale).draw(lcd)
+    }
+
+    /// [`Term::display_immediately_scaled`] for an [`AsyncLcd`]
+    pub async fn display_immediately_async<L: AsyncLcd>(
+        lcd: &mut L,
+        coords: (usize, usize),
+        ch: Char,
+        size: LineSize,
+        scale: usize,
+    ) -> Result<(), L::Error> {
+        Self::cell_window(coords, ch, size, scale)
+            .draw_async(lcd)
+            .await
+    }
+
+    /// Where the cell `(x, y)` goes, and its glyph, see
+    /// [`Term::display_immediately_scaled`]
+    fn cell_window((x, y): (usize, usize), ch: Char, size: LineSize, scale: usize) -> Window {
+        let (scale_x, scale_y) = size.scale();
+        let (scale_x, scale_y) = (scale_x * scale, scale_y * scale);
+        let width = CHAR_WIDTH * scale_x;
+        let height = CHAR_HEIGHT * scale;
+        Window {
+            x: ((x * width) as u16, (x * width + width - 1) as u16),
+            y: ((y * height) as u16, (y * height + height - 1) as u16),
+            pixels: Pixels::Glyph {
+                ch,
+                cols: 0..CHAR_WIDTH,
+                rows: size.glyph_rows(),
+                scale: (scale_x, scale_y),
+            },
+        }
+    }
+
+    /// Everything [`Term::display`] might have to draw, in order: the stale
+    /// tails, then every cell
+    fn jobs() -> impl Iterator<Item = Job> {
+        let tails = (0..HEIGHT).map(Job::Tail);
+        let cells = (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| Job::Cell(x, y)));
+        tails.chain(cells)
+    }
+
+    /// The window to draw for `job`, if it needs drawing
+    fn window(&self, job: Job) -> Option<Window> {
+        match job {
+            Job::Tail(row) => {
+                let (x, y) = self.stale_tail(row)?.window();
+                Some(Window {
+                    x,
+                    y,
+                    pixels: Pixels::Solid(Char::default().background),
+                })
+            }
+            Job::Cell(x, y) => {
+                let (ch, size) = (self.cells[y][x], self.line_sizes[y]);
+                let dirty = !ch.is_flushed() && !ch.is_reserved();
+                (dirty && x < size.visible_cells(WIDTH))
+                    .then(|| Self::cell_window((x, y), ch, size, self.scale))
             }
         }
     }
+
+    /// Remember that `job` has been drawn
+    fn done(&mut self, job: Job) {
+        match job {
+            Job::Tail(row) => self.stale_tails[row] = false,
+            Job::Cell(x, y) => self.cells[y][x].mark_flushed(),
+        }
+    }
+
+    /// [`Term::display`] for an [`AsyncLcd`], which yields after every cell
+    /// so that a whole screen doesn't hold up the other tasks
+    pub async fn display_async<L: AsyncLcd>(&mut self, lcd: &mut L) -> Result<(), L::Error> {
+        for job in Self::jobs() {
+            let Some(window) = self.window(job) else {
+                continue;
+            };
+            window.draw_async(lcd).await?;
+            self.done(job);
+            executor::yield_now().await;
+        }
+        lcd.flush().await
+    }
+
+    /// Draw every cell that has changed since the last `display`. If the
+    /// display fails part way through, the cells we didn't get to are left
+    /// dirty, so calling this again picks up where we left off.
+    pub fn display<L: Lcd>(&mut self, lcd: &mut L) -> Result<(), L::Error> {
+        for job in Self::jobs() {
+            let Some(window) = self.window(job) else {
+                continue;
+            };
+            window.draw(lcd)?;
+            self.done(job);
+        }
+        lcd.flush()
+    }
+}
+
+/// Something [`Term::display`] might have to draw
+#[derive(Clone, Copy)]
+enum Job {
+    /// clear the [`Term::stale_tail`] of this row
+    Tail(usize),
+    /// the cell `(x, y)`
+    Cell(usize, usize),
 }
 
 pub struct ScrollableRow {
@@ -168,7 +624,7 @@ impl ScrollableRow {
 
     /// Update the values in this region of a terminal with the value that we
     /// have scrolled to
-    pub fn stamp<const WIDTH: usize, const HEIGHT: usize>(&self, term: &mut Term<WIDTH, HEIGHT>) {
+    pub fn stamp<const WIDTH: usize, const HEIGHT: usize>(&self, _term: &mut Term<WIDTH, HEIGHT>) {
         todo!()
     }
 
@@ -180,16 +636,28 @@ impl ScrollableRow {
         }
     }
 
-    pub fn display(&self, text: &[u8], lcd: &mut impl Lcd) {
-        // this algorithm is hell...
-        // TODO: make it human-readable
-        if self.width == 0 {
-            return;
-        }
+    /// [`ScrollableRow::display`], but wait for vblank first so that it
+    /// doesn't tear. A row is small enough to write before the refresh
+    /// catches up with us.
+    pub fn display_synced<L: Lcd>(
+        &self,
+        text: &[u8],
+        lcd: &mut L,
+        vsync: &mut impl VSync,
+    ) -> Result<(), L::Error> {
+        vsync.wait_for_vblank();
+        self.display(text, lcd)
+    }
 
+    /// The pieces of the row to draw, left to right: which char, which of
+    /// its columns, and the x of its left edge. Only the first and last can
+    /// be cut short, and pieces with no columns are left out, since a window
+    /// has to be at least 1 column wide.
+    fn pieces<'a>(
+        &'a self,
+        text: &'a [u8],
+    ) -> impl Iterator<Item = (Char, Range<usize>, u16)> + 'a {
         let text_len = text.len() as isize;
-        let y_start = (self.row_index * CHAR_HEIGHT) as u16;
-        let y_end = y_start + CHAR_HEIGHT as u16 - 1;
         let x_start = (self.start * CHAR_WIDTH) as u16;
 
         // idea is to start on the right char and then step from there...
@@ -203,69 +671,293 @@ impl ScrollableRow {
         } as usize;
 
         // we might truncate the first char (and the last) this is the first
+        // column of the first one we draw
         let offset = self.shift % CHAR_WIDTH as isize;
         let startbit = if self.shift <= 0 {
             -offset
         } else {
             CHAR_WIDTH as isize - offset
         } as usize;
-        let endbit = CHAR_WIDTH;
-        let truncated_width = (endbit - startbit) as u16;
-
-        // print the first char truncated
-        let mut letter = [self.background; CHAR_WIDTH * CHAR_HEIGHT];
-        self.new_char(text[startch])
-            .get_letter_pixels(&mut letter, startbit..endbit);
-        lcd.prepare_window((x_start, x_start + truncated_width - 1), (y_start, y_end));
-        lcd.write_rgb(&letter[..truncated_width as usize * CHAR_HEIGHT]);
-
-        // print the rest of the chars not truncated
-        for i in 1..self.width {
-            let ch = text[(startch + i) % text.len()];
-            let x_start = x_start + truncated_width + (CHAR_WIDTH as u16) * (i as u16 - 1);
-            lcd.prepare_window((x_start, x_start + CHAR_WIDTH as u16 - 1), (y_start, y_end));
-            letter = [self.background; CHAR_WIDTH * CHAR_HEIGHT];
-            lcd.write_rgb(&letter);
-            self.new_char(ch)
-                .get_letter_pixels(&mut letter, 0..CHAR_WIDTH);
-            lcd.write_rgb(&letter);
-        }
-
-        // we want to print the start of the last character
-        let (startbit, endbit) = (0, startbit);
-
-        // We need to do this, because preparing a window requires at least 1
-        // column to write to ((x, x), (y1, y2)) is for 1 column, we can't do
-        // ((x, x - 1), (y1, y2))
-        if offset == 0 {
-            return;
+
+        let char_at = move |i: usize| self.new_char(text[(startch + i) % text.len()]);
+        let first = (char_at(0), startbit..CHAR_WIDTH, x_start);
+        let x_start = x_start + (CHAR_WIDTH - startbit) as u16;
+        let x_at = move |i: usize| x_start + (CHAR_WIDTH * (i - 1)) as u16;
+        let middle = (1..self.width).map(move |i| (char_at(i), 0..CHAR_WIDTH, x_at(i)));
+        // and the start of the char that the first one makes room for
+        let last = (char_at(self.width), 0..startbit, x_at(self.width));
+
+        iter::once(first)
+            .chain(middle)
+            .chain(iter::once(last))
+            .filter(|(_, cols, _)| !cols.is_empty())
+    }
+
+    /// The windows to draw [`ScrollableRow::pieces`] in
+    fn windows<'a>(&'a self, text: &'a [u8]) -> impl Iterator<Item = Window> + 'a {
+        let y_start = (self.row_index * CHAR_HEIGHT) as u16;
+        let y = (y_start, y_start + CHAR_HEIGHT as u16 - 1);
+        self.pieces(text).map(move |(ch, cols, x)| Window {
+            x: (x, x + cols.len() as u16 - 1),
+            y,
+            pixels: Pixels::Glyph {
+                ch,
+                cols,
+                rows: 0..CHAR_HEIGHT,
+                scale: (1, 1),
+            },
+        })
+    }
+
+    pub fn display<L: Lcd>(&self, text: &[u8], lcd: &mut L) -> Result<(), L::Error> {
+        if self.width == 0 {
+            return Ok(());
+        }
+
+        for window in self.windows(text) {
+            window.draw(lcd)?;
+        }
+        lcd.flush()
+    }
+
+    /// [`ScrollableRow::display`] for an [`AsyncLcd`], which yields after
+    /// every char
+    pub async fn display_async<L: AsyncLcd>(
+        &self,
+        text: &[u8],
+        lcd: &mut L,
+    ) -> Result<(), L::Error> {
+        if self.width == 0 {
+            return Ok(());
+        }
+
+        for window in self.windows(text) {
+            window.draw_async(lcd).await?;
+            executor::yield_now().await;
         }
+        lcd.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{
+        cell::Cell,
+        convert::Infallible,
+        future::Future,
+        pin::pin,
+        task::{Context, Poll},
+    };
+
+    use super::*;
+    use crate::{lcd::mock::MockLcd, vsync::MockSync};
+
+    #[test]
+    fn rasterise_draws_any_piece_of_a_glyph() {
+        let ch = Char {
+            value: b'A',
+            foreground: Rgb16::WHITE,
+            background: Rgb16::BLACK,
+        };
+        let mut whole = [Rgb16::BLACK; 5 * 4];
+        ch.get_glyph_pixels(&FONT, &mut whole, 3..8, 4..8);
+        assert!(whole.contains(&Rgb16::WHITE));
+
+        let glyph = Pixels::Glyph {
+            ch,
+            cols: 3..8,
+            rows: 4..8,
+            scale: (1, 1),
+        };
+        let mut pieces = [Rgb16::BLACK; 5 * 4];
+        for (i, piece) in pieces.chunks_mut(7).enumerate() {
+            glyph.rasterise(7 * i, piece);
+        }
+        assert_eq!(pieces, whole);
+
+        // and blown up, each pixel is repeated across and then down
+        let glyph = Pixels::Glyph {
+            ch,
+            cols: 3..8,
+            rows: 4..8,
+            scale: (2, 3),
+        };
+        let mut scaled = [Rgb16::BLACK; 10 * 12];
+        glyph.rasterise(0, &mut scaled);
+        for (i, &pixel) in scaled.iter().enumerate() {
+            assert_eq!(pixel, whole[i / 10 / 3 * 5 + i % 10 / 2]);
+        }
+    }
+
+    #[test]
+    fn a_double_width_row_is_clean_after_display() {
+        let mut term = Term::<5, 2>::new();
+        let mut lcd = MockLcd::<16>::new((40, 32));
+        term.display(&mut lcd).unwrap();
+        assert!(!term.is_dirty());
+
+        term.set_line_size(0, LineSize::DoubleWidth);
+        term.set_row_chars(0, b"hello");
+        assert!(term.is_dirty());
+        lcd.clear();
+        term.display(&mut lcd).unwrap();
+        // the tail where the fifth cell was, then the two cells that fit
+        assert_eq!(
+            lcd.windows(),
+            &[((32, 39), (0, 15)), ((0, 15), (0, 15)), ((16, 31), (0, 15))],
+        );
+        assert!(!term.is_dirty());
+
+        lcd.clear();
+        term.display(&mut lcd).unwrap();
+        assert_eq!(lcd.windows(), &[]);
+    }
+
+    /// A [`MockLcd`] that checks we waited for vblank exactly once before
+    /// every window
+    struct Synced<'a> {
+        lcd: MockLcd<8>,
+        waits: &'a Cell<usize>,
+    }
+
+    impl Lcd for Synced<'_> {
+        type Error = Infallible;
+
+        fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error> {
+            assert_eq!(self.waits.get(), 1);
+            Lcd::prepare_window(&mut self.lcd, x, y)
+        }
+
+        fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error> {
+            Lcd::write_rgb(&mut self.lcd, rgb)
+        }
+
+        fn dimensions(&self) -> (u16, u16) {
+            self.lcd.dimensions
+        }
+    }
+
+    #[test]
+    fn display_synced_waits_once_before_the_first_window() {
+        let waits = Cell::new(0);
+        let mut lcd = Synced {
+            lcd: MockLcd::new((80, 16)),
+            waits: &waits,
+        };
+        let mut row = ScrollableRow::new(0, 1, 3, Rgb16::BLACK, Rgb16::WHITE);
+        row.shift(-3);
+        row.display_synced(b"abcd", &mut lcd, &mut MockSync(&waits))
+            .unwrap();
+        assert_eq!(waits.get(), 1);
+        // a char cut short at each end, with the rest in between
+        assert_eq!(
+            lcd.lcd.windows(),
+            &[
+                ((8, 12), (0, 15)),
+                ((13, 20), (0, 15)),
+                ((21, 28), (0, 15)),
+                ((29, 31), (0, 15)),
+            ],
+        );
+    }
+
+    /// Poll `future` to the end, and count how many times it yielded
+    fn run<F: Future>(future: F) -> (F::Output, usize) {
+        let waker = executor::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        let mut yields = 0;
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return (output, yields),
+                Poll::Pending => yields += 1,
+            }
+        }
+    }
+
+    fn dirty_term() -> Term<5, 2> {
+        let mut term = Term::new();
+        term.set_line_size(0, LineSize::DoubleWidth);
+        term.set_row_chars(0, b"hello");
+        term.set_row_chars(1, b"ab");
+        term
+    }
+
+    /// A [`MockLcd`] that fails to start any window after the first few
+    struct Failing {
+        lcd: MockLcd<8>,
+        windows_left: usize,
+    }
+
+    impl Lcd for Failing {
+        type Error = ();
+
+        fn prepare_window(&mut self, x: (u16, u16), y: (u16, u16)) -> Result<(), Self::Error> {
+            self.windows_left = self.windows_left.checked_sub(1).ok_or(())?;
+            Lcd::prepare_window(&mut self.lcd, x, y).map_err(|_| ())
+        }
+
+        fn write_rgb(&mut self, rgb: &[Rgb16]) -> Result<(), Self::Error> {
+            Lcd::write_rgb(&mut self.lcd, rgb).map_err(|_| ())
+        }
+
+        fn dimensions(&self) -> (u16, u16) {
+            self.lcd.dimensions
+        }
+    }
+
+    #[test]
+    fn display_picks_up_where_a_failure_left_off() {
+        let mut lcd = MockLcd::<8>::new((40, 32));
+        dirty_term().display(&mut lcd).unwrap();
+        let all = lcd.windows();
+
+        let mut term = dirty_term();
+        let mut failing = Failing {
+            lcd: MockLcd::new((40, 32)),
+            windows_left: 3,
+        };
+        assert_eq!(term.display(&mut failing), Err(()));
+        assert_eq!(failing.lcd.windows(), &all[..3]);
+        assert!(term.is_dirty());
+
+        // only the cells we didn't get to are drawn again
+        let mut retry = MockLcd::<8>::new((40, 32));
+        term.display(&mut retry).unwrap();
+        assert_eq!(retry.windows(), &all[3..]);
+        assert!(!term.is_dirty());
+    }
+
+    #[test]
+    fn display_async_yields_after_every_window() {
+        let mut lcd = MockLcd::<32>::new((40, 32));
+        dirty_term().display(&mut lcd).unwrap();
+
+        let mut term = dirty_term();
+        let mut async_lcd = MockLcd::<32>::new((40, 32));
+        let (result, yields) = run(term.display_async(&mut async_lcd));
+        result.unwrap();
+        // the tail, two double-width cells and the whole of the second row
+        assert_eq!(async_lcd.windows().len(), 1 + 2 + 5);
+        assert_eq!(async_lcd.windows(), lcd.windows());
+        assert_eq!(yields, async_lcd.windows().len());
+        assert_eq!(async_lcd.flushes, 1);
+        assert!(!term.is_dirty());
+    }
 
-        // wipe area we will be using
-        letter = [self.background; CHAR_WIDTH * CHAR_HEIGHT];
-
-        let rem_width = CHAR_WIDTH as u16 - truncated_width;
-        let x_start = x_start + truncated_width + (CHAR_WIDTH as u16) * (self.width as u16 - 1);
-        lcd.prepare_window((x_start, x_start + rem_width - 1), (y_start, y_end));
-        lcd.write_rgb(&letter[..rem_width as usize * CHAR_HEIGHT]);
-
-        let ch = text[(startch + self.width) % text.len()];
-        self.new_char(ch)
-            .get_letter_pixels(&mut letter, startbit..endbit);
-        lcd.write_rgb(&letter[..rem_width as usize * CHAR_HEIGHT]);
-
-        // for i in (0..rem_width as usize * 16).step_by(rem_width as usize) {
-        //     println!("{:?}", &letter[i..i + rem_width as usize]);
-        // }
-        // println!("char = {:?}", char::from_u32(ch as _));
-        // println!("  [{startbit}..{endbit}]");
-        // println!(
-        //     "  window = {:?}",
-        //     ((x_start, x_start + rem_width - 1), (y_start, y_end))
-        // );
-        // println!(
-        //     "  wrote {} pixels",
-        //     &letter[..rem_width as usize * CHAR_HEIGHT].len()
-        // );
+    #[test]
+    fn scrollable_row_display_async_yields_after_every_char() {
+        let mut row = ScrollableRow::new(1, 0, 3, Rgb16::BLACK, Rgb16::WHITE);
+        row.shift(5);
+        let mut lcd = MockLcd::<8>::new((80, 32));
+        row.display(b"abcd", &mut lcd).unwrap();
+
+        let mut async_lcd = MockLcd::<8>::new((80, 32));
+        let (result, yields) = run(row.display_async(b"abcd", &mut async_lcd));
+        result.unwrap();
+        assert_eq!(async_lcd.windows().len(), 4);
+        assert_eq!(async_lcd.windows(), lcd.windows());
+        assert_eq!(yields, 4);
+        assert_eq!(async_lcd.flushes, 1);
     }
 }
@@ -0,0 +1,195 @@
+use core::ops::Range;
+
+use crate::{
+    font::Font,
+    lcd::{Lcd, Rect, Rgb16},
+    term::{Char, MAX_GLYPH_WIDTH},
+};
+
+/// A proportional font, for drawing text anywhere on the display rather than
+/// on the [`crate::term::Term`] grid. Glyphs are stored as a normal [`Font`]
+/// (left-aligned in their cell), along with how far to move right after
+/// drawing each one. `procure.py --glyph-height` makes the `.adv` files.
+pub struct PropFont<'a> {
+    pub font: Font<'a>,
+    /// advance width of each glyph, starting at `' '`
+    pub advances: &'a [u8],
+}
+
+impl<'a> PropFont<'a> {
+    /// How far to move right after drawing `ch`. Anything without an advance
+    /// is as wide as the font.
+    pub fn advance(&self, ch: u8) -> u16 {
+        ch.checked_sub(b' ')
+            .and_then(|index| self.advances.get(index as usize))
+            .map_or(self.font.width as u16, |&advance| advance as u16)
+    }
+
+    /// The width of `text` in pixels, if it were drawn
+    pub fn measure(&self, text: &[u8]) -> u16 {
+        text.iter().map(|&ch| self.advance(ch)).sum()
+    }
+
+    /// Draw `text` with its top-left corner at `(x, y)`, and return the `x`
+    /// where the next glyph would go. Only the parts of the text inside
+    /// `clip` (and the display) are drawn, so e.g. long station names can
+    /// be cut off at the edge of their column.
+    pub fn draw<L: Lcd>(
+        &self,
+        lcd: &mut L,
+        (x, y): (i32, i32),
+        text: &[u8],
+        foreground: Rgb16,
+        background: Rgb16,
+        clip: Rect,
+    ) -> Result<i32, L::Error> {
+        let (lcd_width, lcd_height) = lcd.dimensions();
+        let clip = clip.intersection(&Rect::new(0, 0, lcd_width, lcd_height));
+        let clip_x = clip.x as i32..clip.x as i32 + clip.width as i32;
+        let clip_y = clip.y as i32..clip.y as i32 + clip.height as i32;
+
+        // rows of the glyphs that are visible, these are the same for every
+        // glyph
+        let rows = (clip_y.start - y).max(0)..(clip_y.end - y).min(self.font.height as i32);
+
+        let mut x = x;
+        for &ch in text {
+            let advance = self.advance(ch) as i32;
+            let cols = (clip_x.start - x).max(0)..(clip_x.end - x).min(advance);
+            if !cols.is_empty() && !rows.is_empty() {
+                let ch = Char {
+                    value: ch,
+                    foreground,
+                    background,
+                };
+                self.draw_glyph(lcd, (x, y), ch, cols, rows.clone())?;
+            }
+
+            x += advance;
+            if x >= clip_x.end {
+                break;
+            }
+        }
+
+        lcd.flush()?;
+        Ok(x)
+    }
+
+    /// Draw `cols` and `rows` of a single glyph (in glyph coordinates), which
+    /// we already know to be visible. Columns past the end of the bitmap,
+    /// but before the advance, are background. The advance can be wider
+    /// than [`MAX_GLYPH_WIDTH`], so each row is written in pieces.
+    fn draw_glyph<L: Lcd>(
+        &self,
+        lcd: &mut L,
+        (x, y): (i32, i32),
+        ch: Char,
+        cols: Range<i32>,
+        rows: Range<i32>,
+    ) -> Result<(), L::Error> {
+        lcd.prepare_window(
+            ((x + cols.start) as u16, (x + cols.end - 1) as u16),
+            ((y + rows.start) as u16, (y + rows.end - 1) as u16),
+        )?;
+
+        let cols = cols.start as usize..cols.end as usize;
+        for row in rows {
+            let row = row as usize;
+            for start in cols.clone().step_by(MAX_GLYPH_WIDTH) {
+                let piece = start..(start + MAX_GLYPH_WIDTH).min(cols.end);
+                // the bitmap part always comes first, the rest stays background
+                let bitmap_cols = piece.start.min(self.font.width)..piece.end.min(self.font.width);
+                let mut line = [ch.background; MAX_GLYPH_WIDTH];
+                ch.get_glyph_pixels(&self.font, &mut line, bitmap_cols, row..row + 1);
+                lcd.write_rgb(&line[..piece.len()])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{font::GlyphFormat, lcd::mock::MockLcd};
+
+    /// 4x2 glyphs for `' '`, `'!'` and `'"'`, with `'"'` wider than a
+    /// whole line of [`MAX_GLYPH_WIDTH`]
+    const FONT: PropFont = PropFont {
+        font: Font {
+            data: &[0x00, 0x66, 0xff],
+            format: GlyphFormat::Mono,
+            width: 4,
+            height: 2,
+        },
+        advances: &[3, 5, MAX_GLYPH_WIDTH as u8 + 8],
+    };
+
+    const DISPLAY: Rect = Rect::new(0, 0, 100, 50);
+
+    fn draw(lcd: &mut MockLcd<8>, pos: (i32, i32), text: &[u8], clip: Rect) -> i32 {
+        FONT.draw(lcd, pos, text, Rgb16::WHITE, Rgb16::BLACK, clip)
+            .unwrap()
+    }
+
+    #[test]
+    fn advance_widths() {
+        assert_eq!(FONT.advance(b' '), 3);
+        assert_eq!(FONT.advance(b'!'), 5);
+        // past the end of `advances`, or not printable, is the font's width
+        assert_eq!(FONT.advance(b'#'), 4);
+        assert_eq!(FONT.advance(b'\n'), 4);
+        assert_eq!(FONT.measure(b" !#"), 12);
+        assert_eq!(FONT.measure(b""), 0);
+
+        let mut lcd = MockLcd::new((100, 50));
+        assert_eq!(draw(&mut lcd, (10, 20), b" !#", DISPLAY), 22);
+        assert_eq!(
+            lcd.windows(),
+            &[
+                ((10, 12), (20, 21)),
+                ((13, 17), (20, 21)),
+                ((18, 21), (20, 21))
+            ]
+        );
+        assert_eq!(lcd.flushes, 1);
+    }
+
+    #[test]
+    fn only_the_clip_is_drawn() {
+        let mut lcd = MockLcd::new((100, 50));
+        // the top row and the left of `' '` are outside, and we stop at the
+        // end of `'!'` without drawing `'#'`
+        let clip = Rect::new(12, 21, 6, 10);
+        assert_eq!(draw(&mut lcd, (10, 20), b" !#", clip), 18);
+        assert_eq!(lcd.windows(), &[((12, 12), (21, 21)), ((13, 17), (21, 21))]);
+
+        // a glyph that's all to the left is skipped, but still advances
+        lcd.clear();
+        assert_eq!(draw(&mut lcd, (5, 20), b" !", clip), 13);
+        assert_eq!(lcd.windows(), &[((12, 12), (21, 21))]);
+
+        // and the clip is cut down to the display
+        lcd.clear();
+        let clip = Rect::new(95, 0, 20, 20);
+        assert_eq!(draw(&mut lcd, (94, 0), b"!!", clip), 104);
+        assert_eq!(lcd.windows(), &[((95, 98), (0, 1)), ((99, 99), (0, 1))]);
+        lcd.clear();
+        assert_eq!(draw(&mut lcd, (-3, -1), b"!", DISPLAY), 2);
+        assert_eq!(lcd.windows(), &[((0, 1), (0, 0))]);
+    }
+
+    #[test]
+    fn glyphs_wider_than_a_line() {
+        let mut lcd = MockLcd::new((100, 50));
+        assert_eq!(draw(&mut lcd, (0, 0), b"\"", DISPLAY), 40);
+        assert_eq!(lcd.windows(), &[((0, 39), (0, 1))]);
+        assert_eq!(lcd.pixels, 2 * 40);
+
+        lcd.clear();
+        let clip = Rect::new(2, 0, 34, 1);
+        assert_eq!(draw(&mut lcd, (0, 0), b"\"", clip), 40);
+        assert_eq!(lcd.windows(), &[((2, 35), (0, 0))]);
+        assert_eq!(lcd.pixels, 34);
+    }
+}
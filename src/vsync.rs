@@ -0,0 +1,77 @@
+//! Waiting for the panel's vertical blanking, so that we write to RAM while
+//! it isn't being read out. Otherwise fast updates (like a
+//! [`crate::term::ScrollableRow`]) tear, half old frame and half new.
+//!
+//! The controller tells us with its TE (tearing effect) output, once it's
+//! turned on with e.g. [`crate::st7789::St7789::set_tearing_effect`].
+
+use embedded_hal::{delay::DelayNs, digital::InputPin};
+
+/// Something that knows when the panel has just finished a refresh. Tearing
+/// is only cosmetic, so if anything goes wrong we stop waiting and let the
+/// write go ahead, rather than fail or hang.
+pub trait VSync {
+    /// Block until the start of the next vertical blanking period
+    fn wait_for_vblank(&mut self);
+}
+
+/// The controller's TE line on a GPIO. TE is high during vblank, so we wait
+/// for it to go high rather than just for it to be high, or we might catch
+/// the end of one.
+pub struct TePin<P: InputPin, D: DelayNs> {
+    pub pin: P,
+    pub delay: D,
+    /// give up after this long, e.g. if `TEON` was never sent. A frame is
+    /// about 17ms at 60Hz.
+    pub timeout_us: u32,
+}
+
+impl<P: InputPin, D: DelayNs> TePin<P, D> {
+    pub fn new(pin: P, delay: D) -> Self {
+        Self {
+            pin,
+            delay,
+            timeout_us: 20_000,
+        }
+    }
+
+    /// Wait for the pin to be `high` (or not), and return how much of
+    /// `budget_us` is left, or `None` if it ran out
+    fn wait_until(&mut self, high: bool, budget_us: u32) -> Option<u32> {
+        for waited in 0..budget_us {
+            // a pin we can't read might as well be in the state we want
+            if self.pin.is_high().unwrap_or(high) == high {
+                return Some(budget_us - waited);
+            }
+            self.delay.delay_us(1);
+        }
+        None
+    }
+}
+
+impl<P: InputPin, D: DelayNs> VSync for TePin<P, D> {
+    fn wait_for_vblank(&mut self) {
+        if let Some(left) = self.wait_until(false, self.timeout_us) {
+            self.wait_until(true, left);
+        }
+    }
+}
+
+/// For panels without a TE line, which never waits
+pub struct NoSync;
+
+impl VSync for NoSync {
+    fn wait_for_vblank(&mut self) {}
+}
+
+/// Counts how many times we waited, in a `Cell` that the [`crate::lcd::Lcd`]
+/// being synced can look at too, for checking that we wait before writing
+#[cfg(test)]
+pub struct MockSync<'a>(pub &'a core::cell::Cell<usize>);
+
+#[cfg(test)]
+impl VSync for MockSync<'_> {
+    fn wait_for_vblank(&mut self) {
+        self.0.set(self.0.get() + 1);
+    }
+}